@@ -29,7 +29,7 @@ def main():
                     # Extract fields
                     attack_types = data.get('attack_types', [])
                     attack_vectors = data.get('attack_vectors', [])
-                    urgency = data.get('urgency', ['', ''])
+                    urgency = data.get('urgency', {})
                     targets = data.get('targets', [])
                     locations = data.get('locations', [])
                     expiration_date_str = data.get('expiration_date', '')
@@ -59,7 +59,7 @@ def main():
                     cursor.execute(sql, (
                         json.dumps(attack_types),  # Convert list to JSON
                         json.dumps(attack_vectors),  # Convert list to JSON
-                        json.dumps(urgency),  # Convert list to JSON
+                        json.dumps(urgency),  # Convert object to JSON
                         json.dumps(targets),  # Convert list to JSON
                         json.dumps(locations),  # Convert list to JSON
                         expiration_date  # Can be None
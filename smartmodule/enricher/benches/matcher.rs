@@ -0,0 +1,38 @@
+//! Compares the single-pass keyword matcher against scanning the text once
+//! per keyword, on a pulse with a 50KB description. Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use enricher::{attack_type_keywords, attack_type_matcher, contains_word, enrich_record, normalize, OTXRecord};
+
+fn large_description() -> String {
+    let filler = "the actor staged payloads on compromised hosts and rotated infrastructure. ";
+    let mut description = filler.repeat(50 * 1024 / filler.len());
+    description.push_str("final stage deploys ransomware after spearphishing");
+    description
+}
+
+fn attack_type_matching(c: &mut Criterion) {
+    let description = large_description();
+    let text = normalize(&description);
+    let record = OTXRecord::builder()
+        .with_name("Large pulse")
+        .with_description(description)
+        .with_tags(["apt"])
+        .build();
+
+    let mut group = c.benchmark_group("attack_types_50kb");
+    group.bench_function("per_keyword_scan", |b| {
+        b.iter(|| {
+            attack_type_keywords()
+                .iter()
+                .filter(|(keyword, _)| contains_word(&text, keyword))
+                .count()
+        })
+    });
+    group.bench_function("matcher", |b| b.iter(|| attack_type_matcher().find(&[&text])));
+    group.bench_function("enrich_record", |b| b.iter(|| enrich_record(&record)));
+    group.finish();
+}
+
+criterion_group!(benches, attack_type_matching);
+criterion_main!(benches);
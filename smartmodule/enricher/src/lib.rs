@@ -1,54 +1,682 @@
 
-use std::collections::HashMap;
-use fluvio_smartmodule::{smartmodule, Result, SmartModuleRecord, RecordData};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::OnceLock;
+use aho_corasick::AhoCorasick;
+use regex::{Regex, RegexBuilder};
+use fluvio_smartmodule::{
+    dataplane::smartmodule::SmartModuleExtraParams, eyre, smartmodule, RecordData, Result,
+    SmartModuleRecord,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::{from_slice, to_vec};
 
 
-#[smartmodule(array_map)]
+static CONFIG: OnceLock<EnricherConfig> = OnceLock::new();
+
+#[smartmodule(init)]
+fn init(params: SmartModuleExtraParams) -> Result<()> {
+    let config = EnricherConfig::from_params(&params)?;
+    CONFIG.set(config).map_err(|_| eyre::eyre!("enricher already initialized"))
+}
+
+/// Keeps a pulse only if one of its results has an active indicator, so
+/// empty and fully stale pulses can be dropped ahead of `array_map`. Input
+/// that fails to deserialize is kept so `array_map` can report it. Built with
+/// the `filter` feature, this is the module's only transform, taking
+/// precedence over `summarize`.
+#[cfg_attr(feature = "filter", smartmodule(filter))]
+pub fn filter(record: &SmartModuleRecord) -> Result<bool> {
+    Ok(parse_results(record.value.as_ref()).0.iter().any(|parsed| match parsed {
+        Ok(result) => result.indicators.iter().any(|ind| ind.is_active == 1),
+        Err(_) => true,
+    }))
+}
+
+#[cfg_attr(not(any(feature = "bincode", feature = "filter", feature = "summarize")), smartmodule(array_map))]
 pub fn array_map(record: &SmartModuleRecord) -> Result<Vec<(Option<RecordData>, RecordData)>> {
-    
-    let otx_pulse: OTXPulse = from_slice(record.value.as_ref())?;
-    let mut enriched_records: Vec<(Option<RecordData>, RecordData)> = vec![];
+    enrich_records(record, config(), |enriched| Ok(to_vec(enriched)?))
+}
+
+/// Like `array_map` but writes each enriched record with
+/// `EnrichedThreatRecord::to_bincode`. Error markers stay JSON. Built with the
+/// `bincode` feature, this is the module's array_map instead of `array_map`.
+#[cfg(feature = "bincode")]
+#[cfg_attr(not(any(feature = "filter", feature = "summarize")), smartmodule(array_map))]
+pub fn array_map_bincode(record: &SmartModuleRecord) -> Result<Vec<(Option<RecordData>, RecordData)>> {
+    enrich_records(record, config(), EnrichedThreatRecord::to_bincode)
+}
 
-    for result in otx_pulse.results.iter() {
+/// `source_id` of the record emitted for a pulse without results.
+pub const EMPTY_PULSE_SOURCE_ID: &str = "<empty>";
 
-        let attack_types = classify_attack_types(result);
+/// The record emitted for a pulse without results when `emit_empty` is set:
+/// every classification `Unknown`, every list empty and `source_id` set to
+/// `EMPTY_PULSE_SOURCE_ID`.
+fn empty_pulse_record(config: &EnricherConfig) -> EnrichedThreatRecord {
+    EnrichedThreatRecord {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        source: config.source.clone(),
+        feed_url: config.feed_url.clone(),
+        partial_feed: false,
+        source_id: EMPTY_PULSE_SOURCE_ID.to_string(),
+        source_name: String::new(),
+        pulse_id: String::new(),
+        pulse_name: String::new(),
+        revision: 0,
+        created: String::new(),
+        modified: String::new(),
+        age_hours: None,
+        author: String::new(),
+        attack_types: vec![Scored::unknown(AttackType::Unknown)],
+        attack_type_scores: vec![(AttackType::Unknown, 0.0)],
+        attack_vectors: vec![Scored::unknown(AttackVector::Unknown)],
+        attack_vector_matches: vec![],
+        dominant_vector: AttackVector::Unknown,
+        urgency: UrgencyAssessment { temperature: Temperature::Unknown, severity: Severity::Unknown },
+        urgency_score: 0,
+        threat_score: 0,
+        overall_severity: "Info".to_string(),
+        tlp: Tlp::Unknown,
+        restricted: false,
+        targets: vec![Scored::unknown(Target::Unknown)],
+        os_targets: vec![OsTarget::Unknown],
+        sectors: vec![],
+        raw_industries: vec![],
+        industries: vec![],
+        locations: vec!["Unknown".to_string()],
+        malware_families: vec![],
+        tags: vec![],
+        cve_ids: vec![],
+        cvss_score: None,
+        mitre_techniques: vec![],
+        mitre_tactics: vec![],
+        adversary: None,
+        threat_actor: None,
+        iocs: vec![],
+        references: vec![],
+        active_indicator_count: 0,
+        expired_indicator_count: 0,
+        expiration_date: String::new(),
+        earliest_expiration: String::new(),
+        evidence: None,
+        hits: vec![],
+        dropped_references: None,
+        truncated: false,
+    }
+}
 
-        let attack_vectors = classify_attack_vectors(result);
+/// Rolls a whole pulse up into one `PulseSummary`. Results that fail to
+/// deserialize are left out of the summary. Built with the `summarize`
+/// feature, this is the module's only transform.
+#[cfg_attr(all(feature = "summarize", not(feature = "filter")), smartmodule(map))]
+pub fn summarize(record: &SmartModuleRecord) -> Result<(Option<RecordData>, RecordData)> {
+    let (results, page) = parse_results(record.value.as_ref());
+    let results: Vec<OTXRecord> = results.into_iter().filter_map(|parsed| parsed.ok()).collect();
+    Ok((None, to_vec(&summarize_pulse(&results, &page))?.into()))
+}
 
-        let urgency = classify_urgency(result);
+/// Enriches every result of a pulse, writing each enriched record with
+/// `encode`.
+fn enrich_records(
+    record: &SmartModuleRecord,
+    config: &EnricherConfig,
+    encode: impl Fn(&EnrichedThreatRecord) -> Result<Vec<u8>>,
+) -> Result<Vec<(Option<RecordData>, RecordData)>> {
+    let (results, page) = parse_results(record.value.as_ref());
+    let partial_feed = page.next.is_some();
+    if results.is_empty() && config.emit_empty {
+        return Ok(vec![(None, encode(&empty_pulse_record(config))?.into())]);
+    }
+    let mut enriched_records: Vec<(Option<RecordData>, RecordData)> = vec![];
+    // The encoded records emitted so far, so repeated results in one pulse
+    // are only emitted once.
+    let mut emitted: HashSet<Vec<u8>> = HashSet::new();
 
-        let targets = classify_targets(result);
+    for parsed in results.iter() {
 
-        let locations = if result.targeted_countries.is_empty() {
-            vec!["Unknown".to_string()]
-        } else {
-            result.targeted_countries.clone()
+        // Malformed input becomes an error marker in the output stream
+        // instead of failing the whole batch.
+        let result = match parsed {
+            Ok(result) => result,
+            Err(error) => {
+                enriched_records.push((None, to_vec(error)?.into()));
+                continue;
+            }
         };
 
-        let expiration_date = get_expiration(result).unwrap_or_else(|| "".to_string());
+        let mut enriched_record = enrich_record_with(result, config);
+        enriched_record.partial_feed = partial_feed;
+        let serialized_data = encode(&enriched_record)?;
+        if !emitted.insert(serialized_data.clone()) {
+            continue;
+        }
+        let key = config.key_strategy.key(result, &enriched_record);
+        enriched_records.push((key, serialized_data.into()));
+    }
+    Ok(enriched_records)
+}
+
+
+/// Enriches one result with the settings the module was initialized with.
+/// This is the per-result step of `array_map`, for use outside Fluvio.
+pub fn enrich_record(record: &OTXRecord) -> EnrichedThreatRecord {
+    enrich_record_with(record, config())
+}
+
+/// Enriches every result of a pulse, one record per result in pulse order.
+/// Unlike `array_map`, repeated results are not collapsed.
+pub fn enrich_pulse(pulse: &OTXPulse) -> Vec<EnrichedThreatRecord> {
+    pulse
+        .results
+        .iter()
+        .map(|result| EnrichedThreatRecord { partial_feed: pulse.next.is_some(), ..enrich_record(result) })
+        .collect()
+}
+
+fn enrich_record_with(result: &OTXRecord, config: &EnricherConfig) -> EnrichedThreatRecord {
+    let text = FlattenedText::with_limits(result, config.max_text_bytes, config.max_indicators_scanned);
+
+    let threshold = config.classification_threshold;
+
+    let mut attack_types = classify_attack_types(&text, threshold);
+
+    let attack_type_scores = classify_attack_types_scored(&text);
+
+    let mut attack_vectors = classify_attack_vectors(&text, threshold);
+
+    let mut targets = classify_targets(&text, threshold);
+
+    reconcile_phishing(&attack_types, &mut attack_vectors, &mut targets);
+    attack_types = prune_unknown(attack_types, Scored::unknown(AttackType::Unknown));
+    attack_vectors = prune_unknown(attack_vectors, Scored::unknown(AttackVector::Unknown));
+    targets = prune_unknown(targets, Scored::unknown(Target::Unknown));
+
+    let attack_vector_matches = count_attack_vector_matches(&text, &attack_vectors);
+
+    let dominant_vector = dominant_vector(&attack_vector_matches);
+
+    let urgency = classify_urgency(&text, config);
+
+    let urgency_score = classify_urgency_score(result, urgency);
+
+    let os_targets = classify_os_targets(&text);
+
+    let (sectors, raw_industries) = classify_sectors(&text);
+
+    let industries = classify_industries(result);
+
+    let locations = if result.targeted_countries.is_empty() {
+        vec!["Unknown".to_string()]
+    } else {
+        result.targeted_countries.clone()
+    };
+
+    let malware_families = malware_families(result);
+
+    let tags = normalized_names(&result.tags);
+
+    let cve_ids = cves_within(result, config.max_text_bytes);
+
+    let cvss_score = cvss_score_within(result, config.max_text_bytes);
+
+    let mitre_techniques = mitre_techniques(result);
+
+    let mitre_tactics = mitre_tactics(&mitre_techniques);
+
+    let adversary = adversary(result);
+
+    let threat_actor = threat_actor(result);
+
+    let iocs = extract_iocs(result);
+
+    let (references, dropped_references) = extract_references(result, config.max_references);
+
+    let (active_indicator_count, expired_indicator_count) = count_indicators(result, std::time::SystemTime::now());
+
+    let expiration_date = get_expiration(result).unwrap_or_default();
+
+    let earliest_expiration = get_earliest_expiration(result).unwrap_or_default();
+
+    let mut enriched_record = EnrichedThreatRecord {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        source: config.source.clone(),
+        feed_url: config.feed_url.clone(),
+        partial_feed: false,
+        source_id: result.id.clone(),
+        source_name: result.name.clone(),
+        pulse_id: result.id.clone(),
+        pulse_name: result.name.clone(),
+        revision: result.revision,
+        created: normalize_timestamp(&result.created),
+        modified: normalize_timestamp(&result.modified),
+        age_hours: age_hours(&result.modified, std::time::SystemTime::now()),
+        author: result.author_name.clone(),
+        attack_types,
+        attack_type_scores,
+        attack_vectors,
+        attack_vector_matches,
+        dominant_vector,
+        urgency,
+        urgency_score,
+        threat_score: 0,
+        overall_severity: String::new(),
+        tlp: result.tlp,
+        restricted: result.tlp.is_restricted(),
+        targets,
+        os_targets,
+        sectors,
+        raw_industries,
+        industries,
+        locations,
+        malware_families,
+        tags,
+        cve_ids,
+        cvss_score,
+        mitre_techniques,
+        mitre_tactics,
+        adversary,
+        threat_actor,
+        iocs,
+        references,
+        active_indicator_count,
+        expired_indicator_count,
+        expiration_date,
+        earliest_expiration,
+        evidence: None,
+        hits: vec![],
+        dropped_references: config.evidence.then_some(dropped_references),
+        truncated: text.truncated,
+    };
+    enriched_record.threat_score = compute_threat_score(result, &enriched_record);
+    enriched_record.overall_severity = severity_label(
+        enriched_record.urgency,
+        enriched_record.active_indicator_count,
+        &enriched_record.attack_types,
+    ).to_string();
+    if config.evidence || config.include_provenance {
+        let evidence = classified_evidence(&text, threshold, &enriched_record);
+        if config.include_provenance {
+            enriched_record.hits = provenance_hits(&text, &evidence, &enriched_record);
+        }
+        if config.evidence {
+            enriched_record.evidence = Some(evidence);
+        }
+    }
+    if config.redact_tlp && result.tlp.limits_sharing() {
+        enriched_record.references.clear();
+        enriched_record.evidence = None;
+        enriched_record.hits.clear();
+        enriched_record.dropped_references = None;
+    }
+    enriched_record
+}
+
+/// Summary of a pulse's results. Attack types and targets are distinct and in
+/// declaration order, countries distinct and in order of first appearance,
+/// leaving out `Unknown` labels.
+fn summarize_pulse(results: &[OTXRecord], page: &Page) -> PulseSummary {
+    let threshold = config().classification_threshold;
+    let mut summary = PulseSummary {
+        total_results: results.len(),
+        total_count: page.count.unwrap_or(results.len() as u64),
+        has_next: page.next.is_some(),
+        has_previous: page.previous.is_some(),
+        distinct_attack_types: vec![],
+        distinct_targets: vec![],
+        max_urgency: UrgencyAssessment { temperature: Temperature::Unknown, severity: Severity::Unknown },
+        total_active_indicators: 0,
+        all_countries: vec![],
+    };
+    for result in results {
+        let text = FlattenedText::new(result);
+        for label in classify_attack_types(&text, threshold).into_iter().map(|scored| scored.label) {
+            if label != AttackType::Unknown && !summary.distinct_attack_types.contains(&label) {
+                summary.distinct_attack_types.push(label);
+            }
+        }
+        for label in classify_targets(&text, threshold).into_iter().map(|scored| scored.label) {
+            if label != Target::Unknown && !summary.distinct_targets.contains(&label) {
+                summary.distinct_targets.push(label);
+            }
+        }
+        summary.max_urgency = UrgencyAssessment::max_urgency(summary.max_urgency, classify_urgency(&text, config()));
+        summary.total_active_indicators += count_indicators(result, std::time::SystemTime::now()).0;
+        for country in &result.targeted_countries {
+            if !summary.all_countries.contains(country) {
+                summary.all_countries.push(country.clone());
+            }
+        }
+    }
+    summary.distinct_attack_types.sort();
+    summary.distinct_targets.sort();
+    summary
+}
+
+/// Pulse key of a result's enriched record, so keyed consumers partition and
+/// deduplicate per pulse: the pulse id, or for a pulse without one the hex
+/// FNV-1a hash of its name and modified time. FNV-1a is fixed, unlike
+/// `DefaultHasher`, so keys stay the same across toolchains.
+fn record_key(result: &OTXRecord) -> RecordData {
+    if !result.id.is_empty() {
+        return RecordData::from(result.id.clone().into_bytes());
+    }
+    let bytes = result.name.bytes().chain([0]).chain(result.modified.bytes());
+    RecordData::from(format!("{:016x}", fnv1a(bytes)).into_bytes())
+}
+
+/// 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// What `array_map` keys each enriched record by, set with the `key_strategy`
+/// init param. A record whose chosen attribute is empty is keyed by pulse.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum KeyStrategy {
+    /// `pulse_id`: the pulse, see `record_key`.
+    #[default]
+    PulseId,
+    /// `urgency`: the urgency severity, such as "Critical".
+    Urgency,
+    /// `first_country`: the first targeted country.
+    FirstCountry,
+    /// `adversary`: the adversary the pulse names.
+    Adversary,
+    /// `none`: records are not keyed.
+    None,
+}
+
+impl KeyStrategy {
+    /// The strategy named by a `key_strategy` param value.
+    pub fn from_param(value: &str) -> Option<Self> {
+        match value {
+            "pulse_id" => Some(KeyStrategy::PulseId),
+            "urgency" => Some(KeyStrategy::Urgency),
+            "first_country" => Some(KeyStrategy::FirstCountry),
+            "adversary" => Some(KeyStrategy::Adversary),
+            "none" => Some(KeyStrategy::None),
+            _ => None,
+        }
+    }
 
-        let enriched_record = EnrichedThreatRecord {
-            attack_types,
-            attack_vectors,
-            urgency,
-            targets,
-            locations,
-            expiration_date
+    /// The key of `enriched`, the record enriched from `result`.
+    pub fn key(self, result: &OTXRecord, enriched: &EnrichedThreatRecord) -> Option<RecordData> {
+        let attribute = match self {
+            KeyStrategy::None => return None,
+            KeyStrategy::PulseId => None,
+            KeyStrategy::Urgency => (enriched.urgency.severity != Severity::Unknown)
+                .then(|| format!("{:?}", enriched.urgency.severity)),
+            KeyStrategy::FirstCountry => result.targeted_countries.first().cloned(),
+            KeyStrategy::Adversary => adversary(result),
         };
+        Some(match attribute.filter(|attribute| !attribute.is_empty()) {
+            Some(attribute) => RecordData::from(attribute.into_bytes()),
+            None => record_key(result),
+        })
+    }
+}
+
+/// Pagination fields of a raw pulse. A bare array of results is one page.
+#[derive(Debug, Default)]
+struct Page {
+    count: Option<u64>,
+    previous: Option<String>,
+    next: Option<String>,
+}
+
+/// Reads a pulse, either enveloped as the OTX API sends it or as a bare
+/// array of results. `None` when the value is neither or any result fails
+/// to deserialize.
+fn parse_pulse(value: &[u8]) -> Option<OTXPulse> {
+    from_slice::<OTXPulse>(value)
+        .ok()
+        .or_else(|| from_slice::<Vec<OTXRecord>>(value).ok().map(OTXPulse::from))
+}
 
+/// Splits a raw pulse into its results and its pagination fields, from one
+/// parse. A pulse that fails to deserialize is retried one result at a time,
+/// so a single malformed result doesn't cost the others or the pagination
+/// fields. Each result that still fails, or the whole value if it isn't a
+/// pulse at all, is returned as an `EnrichmentError`.
+fn parse_results(value: &[u8]) -> (Vec<std::result::Result<OTXRecord, EnrichmentError>>, Page) {
+    if let Some(pulse) = parse_pulse(value) {
+        let page = Page { count: Some(pulse.count), previous: pulse.previous, next: pulse.next };
+        return (pulse.results.into_iter().map(Ok).collect(), page);
+    }
 
-        let serialized_data = to_vec(&enriched_record)?;
-        enriched_records.push((None, serialized_data.into()));
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawPulse {
+        Envelope {
+            results: Vec<serde_json::Value>,
+            #[serde(default)]
+            count: Option<u64>,
+            #[serde(default)]
+            previous: Option<String>,
+            #[serde(default)]
+            next: Option<String>,
+        },
+        Bare(Vec<serde_json::Value>),
     }
-    Ok(enriched_records)
+
+    let (results, page) = match from_slice::<RawPulse>(value) {
+        Ok(RawPulse::Envelope { results, count, previous, next }) => (results, Page { count, previous, next }),
+        Ok(RawPulse::Bare(results)) => (results, Page::default()),
+        Err(error) => return (vec![Err(EnrichmentError { error: error.to_string(), raw_len: value.len() })], Page::default()),
+    };
+    let results = results
+        .iter()
+        .map(|result| {
+            OTXRecord::deserialize(result).map_err(|error| EnrichmentError {
+                error: error.to_string(),
+                raw_len: result.to_string().len(),
+            })
+        })
+        .collect();
+    (results, page)
+}
+
+
+/// Enricher Configuration
+
+/// `source` of enriched records when the `source` init param isn't set.
+pub const DEFAULT_SOURCE: &str = "otx";
+
+/// Settings read from the SmartModule init params. Every setting is optional
+/// and the defaults keep the enriched output unchanged.
+#[derive(Debug, Clone)]
+pub struct EnricherConfig {
+    /// `evidence=true` attaches the keyword hits behind each classification.
+    pub evidence: bool,
+    /// `include_provenance=true` attaches which keyword triggered each
+    /// classification, see `ClassificationHit`.
+    pub include_provenance: bool,
+    /// `classification_threshold` overrides the keyword weight a label needs.
+    pub classification_threshold: f32,
+    /// `max_text_bytes` caps how much of a record's text is classified.
+    pub max_text_bytes: usize,
+    /// `max_indicators_scanned` caps how many indicators' text is
+    /// classified, active indicators first. Unlimited when unset; IOCs and
+    /// expirations always read every indicator.
+    pub max_indicators_scanned: Option<usize>,
+    /// `max_references` caps how many references a record carries.
+    pub max_references: usize,
+    /// `hot_window_days` sets how recent indicators must be to count as hot.
+    pub hot_window_days: u64,
+    /// `hot_active_ratio` sets the share of active indicators a hot pulse
+    /// must exceed.
+    pub hot_active_ratio: f32,
+    /// `hot_threshold` sets how many more indicators a hot pulse must have
+    /// flagged active than inactive.
+    pub hot_threshold: i64,
+    /// `min_active_indicators` sets the fewest active indicators a hot pulse
+    /// must have.
+    pub min_active_indicators: usize,
+    /// `extra_attack_keywords` is a JSON object of further attack type
+    /// keywords, such as `{"cobalt strike": "Trojan"}`.
+    pub extra_attack_keywords: HashMap<&'static str, AttackType>,
+    /// `emit_empty=true` emits one placeholder record, see
+    /// `EMPTY_PULSE_SOURCE_ID`, for a pulse without results instead of
+    /// nothing.
+    pub emit_empty: bool,
+    /// `key_strategy` picks what emitted records are keyed by, see
+    /// `KeyStrategy`.
+    pub key_strategy: KeyStrategy,
+    /// `redact_tlp=true` leaves references, evidence and provenance hits out of
+    /// records for amber and red pulses, keeping their classifications.
+    pub redact_tlp: bool,
+    /// `source` labels the feed records come from, so several connectors
+    /// can share one pipeline.
+    pub source: String,
+    /// `feed_url` records where the feed is fetched from.
+    pub feed_url: Option<String>,
+}
+
+impl Default for EnricherConfig {
+    fn default() -> Self {
+        EnricherConfig {
+            evidence: false,
+            include_provenance: false,
+            classification_threshold: DEFAULT_CLASSIFICATION_THRESHOLD,
+            max_text_bytes: DEFAULT_MAX_TEXT_BYTES,
+            max_indicators_scanned: None,
+            max_references: DEFAULT_MAX_REFERENCES,
+            hot_window_days: DEFAULT_HOT_WINDOW_DAYS,
+            hot_active_ratio: DEFAULT_HOT_ACTIVE_RATIO,
+            hot_threshold: DEFAULT_HOT_THRESHOLD,
+            min_active_indicators: DEFAULT_MIN_ACTIVE_INDICATORS,
+            extra_attack_keywords: HashMap::new(),
+            emit_empty: false,
+            key_strategy: KeyStrategy::PulseId,
+            redact_tlp: false,
+            source: DEFAULT_SOURCE.to_string(),
+            feed_url: None,
+        }
+    }
+}
+
+impl EnricherConfig {
+    pub fn from_params(params: &SmartModuleExtraParams) -> Result<Self> {
+        let mut config = EnricherConfig::default();
+        if let Some(evidence) = params.get("evidence") {
+            config.evidence = evidence
+                .parse()
+                .map_err(|_| eyre::eyre!("evidence must be true or false, got {:?}", evidence))?;
+        }
+        if let Some(include_provenance) = params.get("include_provenance") {
+            config.include_provenance = include_provenance
+                .parse()
+                .map_err(|_| eyre::eyre!("include_provenance must be true or false, got {:?}", include_provenance))?;
+        }
+        if let Some(threshold) = params.get("classification_threshold") {
+            config.classification_threshold = threshold
+                .parse()
+                .map_err(|_| eyre::eyre!("classification_threshold must be a number, got {:?}", threshold))?;
+        }
+        if let Some(max_text_bytes) = params.get("max_text_bytes") {
+            config.max_text_bytes = max_text_bytes
+                .parse()
+                .map_err(|_| eyre::eyre!("max_text_bytes must be a byte count, got {:?}", max_text_bytes))?;
+        }
+        if let Some(max_indicators) = params.get("max_indicators_scanned") {
+            config.max_indicators_scanned = Some(max_indicators.parse().map_err(|_| {
+                eyre::eyre!("max_indicators_scanned must be a count, got {:?}", max_indicators)
+            })?);
+        }
+        if let Some(max_references) = params.get("max_references") {
+            config.max_references = max_references
+                .parse()
+                .map_err(|_| eyre::eyre!("max_references must be a count, got {:?}", max_references))?;
+        }
+        if let Some(days) = params.get("hot_window_days") {
+            config.hot_window_days = days
+                .parse()
+                .ok()
+                .filter(|days: &u64| days.checked_mul(86_400).is_some())
+                .ok_or_else(|| eyre::eyre!("hot_window_days must be a number of days, got {:?}", days))?;
+        }
+        if let Some(ratio) = params.get("hot_active_ratio") {
+            config.hot_active_ratio = ratio
+                .parse()
+                .map_err(|_| eyre::eyre!("hot_active_ratio must be a number, got {:?}", ratio))?;
+        }
+        if let Some(threshold) = params.get("hot_threshold") {
+            config.hot_threshold = threshold
+                .parse()
+                .map_err(|_| eyre::eyre!("hot_threshold must be a whole number, got {:?}", threshold))?;
+        }
+        if let Some(count) = params.get("min_active_indicators") {
+            config.min_active_indicators = count
+                .parse()
+                .map_err(|_| eyre::eyre!("min_active_indicators must be a count, got {:?}", count))?;
+        }
+        if let Some(keywords) = params.get("extra_attack_keywords") {
+            config.extra_attack_keywords = parse_extra_keywords(keywords)?;
+        }
+        if let Some(emit_empty) = params.get("emit_empty") {
+            config.emit_empty = emit_empty
+                .parse()
+                .map_err(|_| eyre::eyre!("emit_empty must be true or false, got {:?}", emit_empty))?;
+        }
+        if let Some(redact_tlp) = params.get("redact_tlp") {
+            config.redact_tlp = redact_tlp
+                .parse()
+                .map_err(|_| eyre::eyre!("redact_tlp must be true or false, got {:?}", redact_tlp))?;
+        }
+        if let Some(source) = params.get("source") {
+            if source.trim().is_empty() {
+                return Err(eyre::eyre!("source must not be empty"));
+            }
+            config.source = source.trim().to_string();
+        }
+        if let Some(feed_url) = params.get("feed_url") {
+            config.feed_url = Some(feed_url.trim().to_string()).filter(|feed_url| !feed_url.is_empty());
+        }
+        if let Some(strategy) = params.get("key_strategy") {
+            config.key_strategy = KeyStrategy::from_param(strategy).ok_or_else(|| {
+                eyre::eyre!(
+                    "key_strategy must be one of pulse_id, urgency, first_country, adversary or none, got {:?}",
+                    strategy
+                )
+            })?;
+        }
+        Ok(config)
+    }
+}
+
+/// Parses a JSON object mapping keywords to `AttackType` names. Keywords are
+/// normalized like the built-in tables, and entries naming no attack type are
+/// skipped. The keywords live as long as the module, like the built-in ones.
+fn parse_extra_keywords(json: &str) -> Result<HashMap<&'static str, AttackType>> {
+    let entries: HashMap<String, String> = serde_json::from_str(json)
+        .map_err(|_| eyre::eyre!("extra_attack_keywords must be a JSON object of keyword to attack type, got {:?}", json))?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(keyword, name)| {
+            let attack_type: AttackType = serde_json::from_value(serde_json::Value::String(name)).ok()?;
+            let keyword: &'static str = Box::leak(normalize(&keyword).into_boxed_str());
+            Some((keyword, attack_type))
+        })
+        .collect())
+}
+
+/// The config set by init, or the defaults before init. The defaults are
+/// kept apart so reading them never stops init from setting its own.
+fn config() -> &'static EnricherConfig {
+    static DEFAULT: OnceLock<EnricherConfig> = OnceLock::new();
+    CONFIG.get().unwrap_or_else(|| DEFAULT.get_or_init(EnricherConfig::default))
 }
 
 
 /// Classification Basis Enums
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// An urgency cue named by a keyword: either a temperature or a severity.
+/// Enriched records carry the two apart in an `UrgencyAssessment`, which is
+/// what orders; cues of different kinds don't compare.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum Urgency {
     Hot,
     Cold,
@@ -57,21 +685,287 @@ pub enum Urgency {
     Low
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+impl Urgency {
+    /// The severity this cue names, `None` for hot/cold.
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            Urgency::Critical => Some(Severity::Critical),
+            Urgency::Medium => Some(Severity::Medium),
+            Urgency::Low => Some(Severity::Low),
+            Urgency::Hot | Urgency::Cold => None,
+        }
+    }
+
+    /// The temperature this cue names, `None` for a severity.
+    pub fn temperature(self) -> Option<Temperature> {
+        match self {
+            Urgency::Hot => Some(Temperature::Hot),
+            Urgency::Cold => Some(Temperature::Cold),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a pulse's indicators are live and recent. `Unknown` for a pulse
+/// without indicators. Ordered `Unknown < Cold < Hot`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
+pub enum Temperature {
+    Hot,
+    Cold,
+    Unknown
+}
+
+impl Temperature {
+    /// Position in the ordering, independent of declaration order.
+    fn rank(self) -> u8 {
+        match self {
+            Temperature::Unknown => 0,
+            Temperature::Cold => 1,
+            Temperature::Hot => 2,
+        }
+    }
+}
+
+impl PartialOrd for Temperature {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Temperature {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+/// How serious a pulse's threat is. `Unknown` when a pulse has neither
+/// indicators nor any severity cue. Ordered
+/// `Unknown < Low < Medium < Critical`, so `max()` picks the most severe.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
+pub enum Severity {
+    Critical,
+    Medium,
+    Low,
+    Unknown
+}
+
+impl Severity {
+    /// Position in the ordering, independent of declaration order.
+    fn rank(self) -> u8 {
+        match self {
+            Severity::Unknown => 0,
+            Severity::Low => 1,
+            Severity::Medium => 2,
+            Severity::Critical => 3,
+        }
+    }
+
+    /// The more severe of `a` and `b`.
+    pub fn max_severity(a: Severity, b: Severity) -> Severity {
+        a.max(b)
+    }
+
+    /// The next severity up, exactly one rank higher. `Critical` stays
+    /// `Critical`.
+    pub fn escalate(self) -> Severity {
+        match self {
+            Severity::Unknown => Severity::Low,
+            Severity::Low => Severity::Medium,
+            Severity::Medium | Severity::Critical => Severity::Critical,
+        }
+    }
+}
+
+impl PartialOrd for Severity {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Severity {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+/// Urgency of a pulse: how live it is and how serious. Ordered by severity,
+/// then temperature, so a Critical/Cold pulse outranks a Medium/Hot one.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize)]
+pub struct UrgencyAssessment {
+    pub temperature: Temperature,
+    pub severity: Severity,
+}
+
+impl UrgencyAssessment {
+    /// The more urgent of `a` and `b`.
+    pub fn max_urgency(a: UrgencyAssessment, b: UrgencyAssessment) -> UrgencyAssessment {
+        a.max(b)
+    }
+}
+
+impl PartialOrd for UrgencyAssessment {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for UrgencyAssessment {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.severity, self.temperature).cmp(&(other.severity, other.temperature))
+    }
+}
+
+/// Accepted JSON shapes of an `UrgencyAssessment`: the named object written
+/// today, or the `[temperature, severity]` pair of `Urgency` labels written by
+/// earlier versions.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UrgencyAssessmentRepr {
+    Named(UrgencyAssessmentFields),
+    Tuple(Urgency, Urgency),
+}
+
+#[derive(Deserialize)]
+struct UrgencyAssessmentFields {
+    temperature: Temperature,
+    severity: Severity,
+}
+
+impl<'de> Deserialize<'de> for UrgencyAssessment {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        // Binary formats can't guess between shapes and only ever hold the
+        // named one.
+        if deserializer.is_human_readable() {
+            UrgencyAssessmentRepr::deserialize(deserializer).map(UrgencyAssessment::from)
+        } else {
+            UrgencyAssessmentFields::deserialize(deserializer)
+                .map(|fields| UrgencyAssessment { temperature: fields.temperature, severity: fields.severity })
+        }
+    }
+}
+
+impl From<UrgencyAssessmentRepr> for UrgencyAssessment {
+    fn from(repr: UrgencyAssessmentRepr) -> Self {
+        match repr {
+            UrgencyAssessmentRepr::Named(UrgencyAssessmentFields { temperature, severity }) => {
+                UrgencyAssessment { temperature, severity }
+            }
+            UrgencyAssessmentRepr::Tuple(temperature, severity) => UrgencyAssessment {
+                temperature: temperature.temperature().unwrap_or(Temperature::Cold),
+                severity: severity.severity().unwrap_or(Severity::Unknown),
+            },
+        }
+    }
+}
+
+/// Traffic Light Protocol label of a pulse. TLP 1.0 "white" reads as its
+/// TLP 2.0 successor `Clear`; any label that can't be parsed, or a missing
+/// one, is `Unknown`. Serialized as its lowercase label, see `Tlp::label`.
+#[derive(Debug, Default, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Tlp {
+    Clear,
+    Green,
+    Amber,
+    AmberStrict,
+    Red,
+    #[default]
+    Unknown
+}
+
+impl Tlp {
+    /// Parses a TLP label ignoring case and an optional "TLP:" prefix, so
+    /// "red", "TLP:RED" and "tlp:amber+strict" are all understood.
+    pub fn parse(label: &str) -> Self {
+        let label = label.trim().to_ascii_lowercase();
+        let label = label.strip_prefix("tlp:").unwrap_or(&label).trim();
+        match label {
+            "clear" | "white" => Tlp::Clear,
+            "green" => Tlp::Green,
+            "amber" => Tlp::Amber,
+            "amber+strict" | "amberstrict" => Tlp::AmberStrict,
+            "red" => Tlp::Red,
+            _ => Tlp::Unknown,
+        }
+    }
+
+    /// The canonical TLP 2.0 label without the "TLP:" prefix, such as
+    /// "amber+strict", or "unknown".
+    pub fn label(self) -> &'static str {
+        match self {
+            Tlp::Clear => "clear",
+            Tlp::Green => "green",
+            Tlp::Amber => "amber",
+            Tlp::AmberStrict => "amber+strict",
+            Tlp::Red => "red",
+            Tlp::Unknown => "unknown",
+        }
+    }
+
+    /// Whether the pulse may not be shared beyond its named recipients.
+    pub fn is_restricted(self) -> bool {
+        self == Tlp::Red
+    }
+
+    /// Whether the pulse may only be shared within the recipients'
+    /// organisations or with the named recipients: amber or red.
+    pub fn limits_sharing(self) -> bool {
+        matches!(self, Tlp::Amber | Tlp::AmberStrict | Tlp::Red)
+    }
+}
+
+/// Parses a free-text TLP label, see `Tlp::parse`.
+pub fn parse_tlp(label: &str) -> Tlp {
+    Tlp::parse(label)
+}
+
+impl From<String> for Tlp {
+    fn from(label: String) -> Self {
+        Tlp::parse(&label)
+    }
+}
+
+impl From<Tlp> for String {
+    fn from(tlp: Tlp) -> Self {
+        tlp.label().to_string()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
 pub enum AttackType {
     Ransomware,
     Malware,
     Ddos,
     Botnet,
     Phishing,
+    BusinessEmailCompromise,
     Trojan,
     Spyware,
+    RemoteAccessTrojan,
+    InfoStealer,
     BruteForce,
     SQLInjection,
+    /// Covers cryptomining as well, which reads as this variant.
+    #[serde(alias = "Cryptomining")]
+    Cryptojacking,
+    Wiper,
+    WebSkimming,
+    Defacement,
+    APT,
+    ZeroDay,
+    Exploit,
+    // Schema version 5 wrote these three by variant name.
+    #[serde(rename = "dns_hijacking", alias = "DnsHijacking")]
+    DnsHijacking,
+    #[serde(rename = "typosquatting", alias = "Typosquatting")]
+    Typosquatting,
+    #[serde(rename = "watering_hole", alias = "WateringHole")]
+    WateringHole,
+    Reconnaissance,
     Unknown
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
 pub enum Target {
     WebApp,
     Infrastructure,
@@ -79,16 +973,57 @@ pub enum Target {
     IotDevices,
     UserFocused,
     EmailAttack,
+    MobileDevices,
+    DatabaseSystems,
+    PointOfSale,
     Unknown
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
 pub enum AttackVector {
     Email,
     WebApplication,
     Network,
     CloudService,
     SupplyChain,
+    RemovableMedia,
+    RemoteAccess,
+    DriveByDownload,
+    Dns,
+    Malvertising,
+    Messaging,
+    Unknown
+}
+
+/// Industry sector a pulse targets.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
+pub enum Sector {
+    Finance,
+    Healthcare,
+    Government,
+    Energy,
+    Education,
+    Retail,
+    Telecom,
+    Manufacturing,
+    Technology,
+    Other
+}
+
+/// A normalized entry of a pulse's `industries` field. Industries and
+/// sectors share one vocabulary.
+pub type Industry = Sector;
+
+/// Platform a pulse's malware or exploits run on.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
+pub enum OsTarget {
+    Windows,
+    Linux,
+    MacOS,
+    Android,
+    Ios,
+    /// Routers, network appliances and other embedded firmware.
+    Embedded,
     Unknown
 }
 
@@ -98,37 +1033,139 @@ pub enum AttackVector {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OTXPulse {
     pub results: Vec<OTXRecord>,
+    #[serde(default)]
     pub count: u64,
+    #[serde(default)]
     pub prefetch_pulse_ids: bool,
+    #[serde(default)]
     pub t: u32,
+    #[serde(default)]
     pub t2: f64,
+    #[serde(default)]
     pub t3: f64,
+    #[serde(default)]
     pub previous: Option<String>,
+    #[serde(default)]
     pub next: Option<String>,
 }
-#[derive(Debug, Clone, Serialize, Deserialize)]
+
+impl From<Vec<OTXRecord>> for OTXPulse {
+    /// A single-page pulse holding `results`, for exports that write the
+    /// results as a bare array without the pulse envelope.
+    fn from(results: Vec<OTXRecord>) -> Self {
+        OTXPulse {
+            count: results.len() as u64,
+            results,
+            prefetch_pulse_ids: false,
+            t: 0,
+            t2: 0.0,
+            t3: 0.0,
+            previous: None,
+            next: None,
+        }
+    }
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct OTXRecord {
     pub id: String,
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(default)]
     pub author_name: String,
+    #[serde(default)]
     pub modified: String,
+    #[serde(default)]
     pub created: String,
+    #[serde(default)]
     pub revision: u64,
-    pub tlp: String,
+    #[serde(default)]
+    pub tlp: Tlp,
+    #[serde(default)]
     pub public: u64,
+    #[serde(default)]
     pub adversary: String,
+    #[serde(default)]
     pub indicators: Vec<OTXIndicator>,
+    #[serde(default)]
     pub tags: Vec<String>,
+    #[serde(default)]
     pub targeted_countries: Vec<String>,
+    #[serde(default)]
     pub malware_families: Vec<String>,
+    #[serde(default)]
     pub attack_ids: Vec<String>,
+    #[serde(default)]
     pub references: Vec<String>,
+    #[serde(default)]
     pub industries: Vec<String>,
+    #[serde(default)]
     pub extract_source: Vec<String>,
+    #[serde(default)]
     pub more_indicators: bool
 }
 
+impl OTXRecord {
+    /// Starts a record with every string and list empty, for building input
+    /// in code rather than from a pulse.
+    pub fn builder() -> OTXRecordBuilder {
+        OTXRecordBuilder::default()
+    }
+}
+
+/// Builds an `OTXRecord` one field at a time, see `OTXRecord::builder`.
+/// Fields without a setter keep their defaults.
+#[derive(Debug, Clone, Default)]
+pub struct OTXRecordBuilder {
+    record: OTXRecord,
+}
+
+impl OTXRecordBuilder {
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.record.id = id.into();
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.record.name = name.into();
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.record.description = description.into();
+        self
+    }
+
+    pub fn with_tags<S: Into<String>>(mut self, tags: impl IntoIterator<Item = S>) -> Self {
+        self.record.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_indicators(mut self, indicators: Vec<OTXIndicator>) -> Self {
+        self.record.indicators = indicators;
+        self
+    }
+
+    pub fn with_targeted_countries<S: Into<String>>(mut self, countries: impl IntoIterator<Item = S>) -> Self {
+        self.record.targeted_countries = countries.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_adversary(mut self, adversary: impl Into<String>) -> Self {
+        self.record.adversary = adversary.into();
+        self
+    }
+
+    pub fn with_attack_ids<S: Into<String>>(mut self, attack_ids: impl IntoIterator<Item = S>) -> Self {
+        self.record.attack_ids = attack_ids.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn build(self) -> OTXRecord {
+        self.record
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OTXIndicator {
     pub id: u128,
@@ -147,412 +1184,6621 @@ pub struct OTXIndicator {
 
 /// Enriched Threat Record
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnrichedThreatRecord {
-    pub attack_types: Vec<AttackType>,
-    pub attack_vectors: Vec<AttackVector>,
-    pub urgency: (Urgency, Urgency),
-    pub targets: Vec<Target>,
+    /// Layout version of the record, always `CURRENT_SCHEMA_VERSION` when
+    /// written by this module. See `migrate` for reading older records.
+    pub schema_version: u32,
+    /// The feed the record came from, set by the init params of the same
+    /// names. `source` defaults to `DEFAULT_SOURCE`.
+    pub source: String,
+    pub feed_url: Option<String>,
+    /// Set when the feed has further pages after the one this record came
+    /// from.
+    pub partial_feed: bool,
+    /// Id and name of the record within its source, for OTX the pulse's.
+    pub source_id: String,
+    pub source_name: String,
+    /// Id and name of the originating OTX pulse, shared by every record
+    /// enriched from it. For OTX records these mirror `source_id` and
+    /// `source_name`, under names that stay tied to the pulse.
+    pub pulse_id: String,
+    pub pulse_name: String,
+    /// Revision of the pulse, which grows each time the pulse is edited.
+    pub revision: u64,
+    /// When the pulse was created and last modified, as RFC 3339 UTC when
+    /// the source date parses and verbatim otherwise.
+    pub created: String,
+    pub modified: String,
+    /// Whole hours since the pulse was last modified, `None` when `modified`
+    /// doesn't parse.
+    pub age_hours: Option<u64>,
+    pub author: String,
+    pub attack_types: Vec<Scored<AttackType>>,
+    pub attack_type_scores: Vec<(AttackType, f32)>,
+    pub attack_vectors: Vec<Scored<AttackVector>>,
+    pub attack_vector_matches: Vec<(AttackVector, usize)>,
+    pub dominant_vector: AttackVector,
+    pub urgency: UrgencyAssessment,
+    pub urgency_score: u8,
+    /// Overall threat from 0 to 100, see `compute_threat_score`.
+    pub threat_score: u8,
+    /// One of "Critical", "High", "Medium", "Low" or "Info", see
+    /// `overall_severity`.
+    pub overall_severity: String,
+    pub tlp: Tlp,
+    /// Set for `Tlp::Red` pulses.
+    pub restricted: bool,
+    pub targets: Vec<Scored<Target>>,
+    pub os_targets: Vec<OsTarget>,
+    /// Sectors named by the pulse's industries or its description.
+    pub sectors: Vec<Sector>,
+    /// Industries that map to no known sector, passed through as written.
+    /// Each one also adds `Sector::Other` to `sectors`.
+    pub raw_industries: Vec<String>,
+    /// Sectors named by the pulse's `industries` field alone.
+    pub industries: Vec<Industry>,
     pub locations: Vec<String>,
-    pub expiration_date: String
+    /// Malware families and tags of the pulse, see `normalized_names`.
+    pub malware_families: Vec<String>,
+    pub tags: Vec<String>,
+    pub cve_ids: Vec<String>,
+    pub cvss_score: Option<f32>,
+    pub mitre_techniques: Vec<MitreTechnique>,
+    /// Distinct tactics of the known techniques, in order of first mention.
+    pub mitre_tactics: Vec<String>,
+    /// The adversary the pulse names, see `adversary`.
+    pub adversary: Option<String>,
+    /// `adversary` under its canonical name when it is a known group.
+    pub threat_actor: Option<String>,
+    pub iocs: Vec<Ioc>,
+    /// The pulse's http and https references, see `extract_references`.
+    pub references: Vec<String>,
+    pub active_indicator_count: usize,
+    pub expired_indicator_count: usize,
+    pub expiration_date: String,
+    pub earliest_expiration: String,
+    /// The keyword hits behind the record's classifications, set with
+    /// `evidence`. Hits for labels the record didn't keep are left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<Vec<Evidence>>,
+    /// The keyword or other source behind each classification, set with
+    /// `include_provenance`, see `ClassificationHit`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hits: Vec<ClassificationHit>,
+    /// How many references were dropped as malformed, set along with
+    /// `evidence`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dropped_references: Option<usize>,
+    /// Set when the record's text was over the size cap and only a prefix
+    /// of it was classified.
+    #[serde(default)]
+    pub truncated: bool,
 }
 
+/// Emitted in place of an enriched record for input that could not be
+/// deserialized. `raw_len` is the size in bytes of the rejected JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnrichmentError {
+    pub error: String,
+    pub raw_len: usize,
+}
 
-/// Classification Functions
+/// One record per pulse, emitted by `summarize`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PulseSummary {
+    pub total_results: usize,
+    /// Results across every page of the query, as OTX counts them.
+    pub total_count: u64,
+    /// Whether OTX links a page after or before this one.
+    pub has_next: bool,
+    pub has_previous: bool,
+    pub distinct_attack_types: Vec<AttackType>,
+    pub distinct_targets: Vec<Target>,
+    /// The most urgent result, `Unknown` for a pulse without results.
+    pub max_urgency: UrgencyAssessment,
+    pub total_active_indicators: usize,
+    pub all_countries: Vec<String>,
+}
+
+/// Columns of `EnrichedThreatRecord::to_csv_row`, in order.
+const CSV_HEADER: &str = "schema_version,source,feed_url,partial_feed,source_id,source_name,pulse_id,pulse_name,revision,created,modified,age_hours,author,attack_types,attack_vectors,dominant_vector,\
+severity,temperature,urgency_score,threat_score,overall_severity,tlp,restricted,targets,os_targets,sectors,\
+industries,raw_industries,locations,malware_families,tags,cve_ids,cvss_score,mitre_techniques,\
+mitre_tactics,adversary,threat_actor,iocs,references,active_indicator_count,expired_indicator_count,expiration_date,\
+earliest_expiration,truncated";
+
+impl EnrichedThreatRecord {
+    /// The header line matching `to_csv_row`.
+    pub fn csv_header() -> &'static str {
+        CSV_HEADER
+    }
 
-fn classify_attack_types(record: &OTXRecord) -> Vec<AttackType> {
-    let mut a_types: Vec<AttackType> = vec![];
-    let mut all_text = vec![
-        record.name.as_str(),
-        record.description.as_str(),
-    ];
-    all_text.extend(record.tags.iter().map(String::as_str));
-    all_text.extend(record.indicators.iter().flat_map(|ind: &OTXIndicator| vec![
-        ind.title.as_str(),
-        ind.description.as_str(),
-        ind.role.as_ref().map(String::as_str).unwrap_or("")
-    ]));
-    let flattened = all_text.join(" ").to_lowercase();
-
-    for (keyword, a_type) in attack_type_keywords().iter() {
-        if flattened.contains(keyword) && !a_types.contains(a_type) {
-            a_types.push(*a_type);
+    /// The record as one CSV line without a trailing newline. Enums are
+    /// written as their variant names and lists are joined with `|`; scored
+    /// labels keep only the label, techniques only their ID and IOCs only
+    /// their value. Per-label scores, match counts, evidence and
+    /// provenance hits are left out.
+    pub fn to_csv_row(&self) -> String {
+        fn names<T: std::fmt::Debug>(items: impl IntoIterator<Item = T>) -> String {
+            items.into_iter().map(|item| format!("{:?}", item)).collect::<Vec<_>>().join("|")
         }
+
+        let fields = [
+            self.schema_version.to_string(),
+            self.source.clone(),
+            self.feed_url.clone().unwrap_or_default(),
+            self.partial_feed.to_string(),
+            self.source_id.clone(),
+            self.source_name.clone(),
+            self.pulse_id.clone(),
+            self.pulse_name.clone(),
+            self.revision.to_string(),
+            self.created.clone(),
+            self.modified.clone(),
+            self.age_hours.map(|hours| hours.to_string()).unwrap_or_default(),
+            self.author.clone(),
+            names(self.attack_types.iter().map(|scored| scored.label)),
+            names(self.attack_vectors.iter().map(|scored| scored.label)),
+            format!("{:?}", self.dominant_vector),
+            format!("{:?}", self.urgency.severity),
+            format!("{:?}", self.urgency.temperature),
+            self.urgency_score.to_string(),
+            self.threat_score.to_string(),
+            self.overall_severity.clone(),
+            format!("{:?}", self.tlp),
+            self.restricted.to_string(),
+            names(self.targets.iter().map(|scored| scored.label)),
+            names(&self.os_targets),
+            names(&self.sectors),
+            names(&self.industries),
+            self.raw_industries.join("|"),
+            self.locations.join("|"),
+            self.malware_families.join("|"),
+            self.tags.join("|"),
+            self.cve_ids.join("|"),
+            self.cvss_score.map(|score| score.to_string()).unwrap_or_default(),
+            self.mitre_techniques.iter().map(|technique| technique.id.as_str()).collect::<Vec<_>>().join("|"),
+            self.mitre_tactics.join("|"),
+            self.adversary.clone().unwrap_or_default(),
+            self.threat_actor.clone().unwrap_or_default(),
+            self.iocs.iter().map(|ioc| ioc.value.as_str()).collect::<Vec<_>>().join("|"),
+            self.references.join("|"),
+            self.active_indicator_count.to_string(),
+            self.expired_indicator_count.to_string(),
+            self.expiration_date.clone(),
+            self.earliest_expiration.clone(),
+            self.truncated.to_string(),
+        ];
+        fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(",")
     }
-    if a_types.is_empty() {
-        vec![AttackType::Unknown]
+}
+
+/// Version of the `EnrichedThreatRecord` layout this module writes. Bump it
+/// whenever a field is added, removed or changes shape, and teach `migrate`
+/// to upgrade the previous version.
+pub const CURRENT_SCHEMA_VERSION: u32 = 6;
+
+/// Reads an enriched record written by any schema version up to
+/// `CURRENT_SCHEMA_VERSION`. Records without `schema_version` are version 1,
+/// the layout before records were versioned: plain label lists without
+/// confidences and only some of today's fields. Fields a record lacks take
+/// the values of an empty pulse's record.
+pub fn migrate(value: serde_json::Value) -> Result<EnrichedThreatRecord> {
+    let serde_json::Value::Object(mut fields) = value else {
+        return Err(eyre::eyre!("enriched record must be a JSON object"));
+    };
+    let version = fields.get("schema_version").map_or(Some(1), serde_json::Value::as_u64);
+    match version {
+        Some(1) => {
+            for list in ["attack_types", "attack_vectors", "targets"] {
+                if let Some(serde_json::Value::Array(labels)) = fields.get_mut(list) {
+                    for label in labels.iter_mut().filter(|label| label.is_string()) {
+                        let confidence = if label == "Unknown" { 0.0 } else { 1.0 };
+                        *label = serde_json::json!({ "label": label.take(), "confidence": confidence });
+                    }
+                }
+            }
+        }
+        // Version 3 added `hits`, which reads as empty when missing. Version
+        // 5 wrote `DnsHijacking`, `Typosquatting` and `WateringHole` by
+        // variant name rather than in snake_case; those still read as aliases.
+        Some(2..=5) => {}
+        Some(version) if version == u64::from(CURRENT_SCHEMA_VERSION) => {}
+        _ => return Err(eyre::eyre!("unsupported schema_version {}", fields["schema_version"])),
+    }
+    // Version 4 added `pulse_id` and `pulse_name`, which older records only
+    // carry as `source_id` and `source_name`.
+    for (field, source_field) in [("pulse_id", "source_id"), ("pulse_name", "source_name")] {
+        if let Some(value) = fields.get(source_field).cloned() {
+            fields.entry(field).or_insert(value);
+        }
+    }
+    if version == Some(1) {
+        if let serde_json::Value::Object(defaults) = serde_json::to_value(empty_pulse_record(&EnricherConfig::default()))? {
+            for (field, default) in defaults {
+                fields.entry(field).or_insert(default);
+            }
+        }
+    }
+    fields.insert("schema_version".to_string(), CURRENT_SCHEMA_VERSION.into());
+    Ok(serde_json::from_value(serde_json::Value::Object(fields))?)
+}
+
+#[cfg(feature = "bincode")]
+impl EnrichedThreatRecord {
+    /// The record in bincode, a more compact encoding than JSON.
+    pub fn to_bincode(&self) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        BincodeRecord::serialize(self, &mut bincode::Serializer::new(&mut bytes, bincode::options()))?;
+        Ok(bytes)
+    }
+
+    /// Reads a record written by `to_bincode`.
+    pub fn from_bincode(bytes: &[u8]) -> Result<EnrichedThreatRecord> {
+        Ok(BincodeRecord::deserialize(&mut bincode::Deserializer::from_slice(bytes, bincode::options()))?)
+    }
+}
+
+/// Field layout of `EnrichedThreatRecord` in bincode. Bincode can't skip
+/// fields, so unlike the JSON layout `evidence` and `hits` are always
+/// written.
+#[cfg(feature = "bincode")]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "EnrichedThreatRecord")]
+struct BincodeRecord {
+    schema_version: u32,
+    source: String,
+    feed_url: Option<String>,
+    partial_feed: bool,
+    source_id: String,
+    source_name: String,
+    pulse_id: String,
+    pulse_name: String,
+    revision: u64,
+    created: String,
+    modified: String,
+    age_hours: Option<u64>,
+    author: String,
+    attack_types: Vec<Scored<AttackType>>,
+    attack_type_scores: Vec<(AttackType, f32)>,
+    attack_vectors: Vec<Scored<AttackVector>>,
+    attack_vector_matches: Vec<(AttackVector, usize)>,
+    dominant_vector: AttackVector,
+    urgency: UrgencyAssessment,
+    urgency_score: u8,
+    threat_score: u8,
+    overall_severity: String,
+    tlp: Tlp,
+    restricted: bool,
+    targets: Vec<Scored<Target>>,
+    os_targets: Vec<OsTarget>,
+    sectors: Vec<Sector>,
+    raw_industries: Vec<String>,
+    industries: Vec<Industry>,
+    locations: Vec<String>,
+    malware_families: Vec<String>,
+    tags: Vec<String>,
+    cve_ids: Vec<String>,
+    cvss_score: Option<f32>,
+    mitre_techniques: Vec<MitreTechnique>,
+    mitre_tactics: Vec<String>,
+    adversary: Option<String>,
+    threat_actor: Option<String>,
+    iocs: Vec<Ioc>,
+    references: Vec<String>,
+    active_indicator_count: usize,
+    expired_indicator_count: usize,
+    expiration_date: String,
+    earliest_expiration: String,
+    evidence: Option<Vec<Evidence>>,
+    hits: Vec<ClassificationHit>,
+    dropped_references: Option<usize>,
+    truncated: bool,
+}
+
+/// Quotes a CSV field holding a comma, quote or line break, doubling any
+/// quotes inside it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
     } else {
-        a_types
+        field.to_string()
     }
 }
 
-fn classify_attack_vectors(record: &OTXRecord) -> Vec<AttackVector> {
-    let mut a_vectors: Vec<AttackVector> = vec![];
-    let mut all_text = vec![
-        record.name.as_str(),
-        record.description.as_str(),
-    ];
-    all_text.extend(record.tags.iter().map(String::as_str));
-    all_text.extend(record.indicators.iter().flat_map(|ind: &OTXIndicator| vec![
-        ind.title.as_str(),
-        ind.description.as_str(),
-    ]));
-    let flattened = all_text.join(" ").to_lowercase();
+/// An indicator of compromise carried over from an active OTX indicator.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ioc {
+    pub value: String,
+    pub ioc_type: IocType,
+}
+
+/// Kind of indicator, derived from the OTX indicator `type`.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum IocType {
+    IPv4,
+    IPv6,
+    Domain,
+    Url,
+    FileHashMd5,
+    FileHashSha1,
+    FileHashSha256,
+    Email,
+    Other,
+}
 
-    for (keyword, a_vector) in attack_vector_keywords().iter() {
-        if flattened.contains(keyword) && !a_vectors.contains(a_vector) {
-            a_vectors.push(*a_vector);
+impl IocType {
+    /// Maps an OTX indicator type such as "FileHash-SHA256" or "hostname",
+    /// ignoring case. Types without a dedicated variant become `Other`.
+    pub fn from_otx_type(type_: &str) -> Self {
+        match type_.to_ascii_lowercase().as_str() {
+            "ipv4" => IocType::IPv4,
+            "ipv6" => IocType::IPv6,
+            "domain" | "hostname" => IocType::Domain,
+            "url" | "uri" => IocType::Url,
+            "filehash-md5" => IocType::FileHashMd5,
+            "filehash-sha1" => IocType::FileHashSha1,
+            "filehash-sha256" => IocType::FileHashSha256,
+            "email" => IocType::Email,
+            _ => IocType::Other,
         }
     }
-    if a_vectors.is_empty() {
-        vec![AttackVector::Unknown]
-    } else {
-        a_vectors
+}
+
+/// A MITRE ATT&CK technique referenced by the pulse. `name` and `tactic` are
+/// `None` for technique IDs the embedded table doesn't know.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MitreTechnique {
+    pub id: String,
+    pub name: Option<String>,
+    pub tactic: Option<String>,
+}
+
+/// A classification label with how strongly the record supports it, from
+/// 0.0 (no keyword evidence) to 1.0.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scored<T> {
+    pub label: T,
+    pub confidence: f32,
+}
+
+impl<T> Scored<T> {
+    /// The fallback label used when nothing matched.
+    pub fn unknown(label: T) -> Self {
+        Scored { label, confidence: 0.0 }
     }
 }
 
-fn classify_urgency(record: &OTXRecord) -> (Urgency, Urgency) {
-    let mut urgency_info: (Urgency, Urgency) = (Urgency::Cold, Urgency::Low); 
-    let mut all_text = vec![
-        record.name.as_str(),
-        record.description.as_str(),
-    ];
-    all_text.extend(record.tags.iter().map(String::as_str));
-    let tipper: i32 = record.indicators.iter().map(|ind: &OTXIndicator| {
-        if ind.is_active == 1 {
-            1
-        } else {
-            -1
+/// Strips the confidence from scored classifications.
+pub fn labels<T: Copy>(scored: &[Scored<T>]) -> Vec<T> {
+    scored.iter().map(|scored| scored.label).collect()
+}
+
+/// A single keyword hit backing one classification.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub classification: Classification,
+    pub keyword: String,
+    pub source: TextSource,
+    /// Set when a whole tag matched a keyword or tag alias exactly, rather
+    /// than a keyword being found within free text.
+    #[serde(default)]
+    pub exact_tag: bool,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum Classification {
+    AttackType(AttackType),
+    AttackVector(AttackVector),
+    Urgency(Urgency),
+    Target(Target),
+}
+
+/// Which keyword triggered a classification, as plain strings: `category` is
+/// `attack_type`, `attack_vector`, `urgency` or `target` and `tag` the label
+/// as it appears in the enriched record. Labels no keyword backs name their
+/// source in `keyword` instead, as `malware_family:lockbit`,
+/// `technique:T1486`, `adversary:<name>`, `cve_year:2023`,
+/// `lookalike_domain:<domain>`, `indicator_type:url` or `attack_type:phishing`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationHit {
+    pub category: String,
+    pub tag: String,
+    pub keyword: String,
+}
+
+impl ClassificationHit {
+    fn new(classification: Classification, keyword: String) -> Self {
+        let (category, tag) = match classification {
+            Classification::AttackType(label) => ("attack_type", serialized_label(label)),
+            Classification::AttackVector(label) => ("attack_vector", serialized_label(label)),
+            Classification::Urgency(label) => ("urgency", serialized_label(label)),
+            Classification::Target(label) => ("target", serialized_label(label)),
+        };
+        ClassificationHit { category: category.to_string(), tag, keyword }
+    }
+}
+
+impl From<&Evidence> for ClassificationHit {
+    fn from(evidence: &Evidence) -> Self {
+        ClassificationHit::new(evidence.classification, evidence.keyword.clone())
+    }
+}
+
+/// A unit enum variant's serialized name, honouring serde renames.
+fn serialized_label<T: Serialize>(label: T) -> String {
+    match serde_json::to_value(label) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+/// Provenance hits for evidence, one per category, tag and keyword whichever
+/// fields the keyword was found in.
+fn classification_hits(evidence: &[Evidence]) -> Vec<ClassificationHit> {
+    let mut hits: Vec<ClassificationHit> = vec![];
+    for hit in evidence.iter().map(ClassificationHit::from) {
+        if !hits.contains(&hit) {
+            hits.push(hit);
+        }
+    }
+    hits
+}
+
+/// `classification_hits` for `evidence`, followed by a hit naming the source
+/// of each of `enriched`'s labels that no keyword backs.
+fn provenance_hits(text: &FlattenedText, evidence: &[Evidence], enriched: &EnrichedThreatRecord) -> Vec<ClassificationHit> {
+    let mut hits = classification_hits(evidence);
+    let classifications = enriched.attack_types.iter().map(|scored| Classification::AttackType(scored.label))
+        .chain(enriched.attack_vectors.iter().map(|scored| Classification::AttackVector(scored.label)))
+        .chain(enriched.targets.iter().map(|scored| Classification::Target(scored.label)));
+    for classification in classifications {
+        if evidence.iter().any(|hit| hit.classification == classification) {
+            continue;
+        }
+        if let Some(source) = label_source(text, classification) {
+            hits.push(ClassificationHit::new(classification, source));
+        }
+    }
+    hits
+}
+
+/// Where a label that no keyword backs came from, checked in the order the
+/// classifiers add them. `None` for `Unknown` placeholders and urgency cues.
+fn label_source(text: &FlattenedText, classification: Classification) -> Option<String> {
+    let record = text.record;
+    match classification {
+        Classification::AttackType(label) => record.malware_families
+            .iter()
+            .find(|family| malware_family_type(family) == Some(label))
+            .map(|family| format!("malware_family:{}", family.to_lowercase()))
+            .or_else(|| {
+                technique_ids(record)
+                    .into_iter()
+                    .find(|id| technique_attack_type(id) == Some(label))
+                    .map(|id| format!("technique:{}", id))
+            })
+            .or_else(|| {
+                (label == AttackType::APT && is_attributed(record))
+                    .then(|| adversary(record))
+                    .flatten()
+                    .map(|adversary| format!("adversary:{}", adversary))
+            })
+            .or_else(|| {
+                (cve_attack_type(text) == Some(label))
+                    .then(|| cve_years(text).into_iter().max())
+                    .flatten()
+                    .map(|year| format!("cve_year:{}", year))
+            })
+            .or_else(|| {
+                (label == AttackType::Typosquatting && has_typosquat_indicators(record))
+                    .then(|| record.indicators.iter().find(|ind| is_lookalike_domain(&ind.indicator)))
+                    .flatten()
+                    .map(|ind| format!("lookalike_domain:{}", ind.indicator))
+            }),
+        Classification::AttackVector(label) => record.indicators
+            .iter()
+            .find(|ind| indicator_type_vector(&ind.type_) == Some(label))
+            .map(|ind| format!("indicator_type:{}", ind.type_.to_ascii_lowercase()))
+            .or_else(|| (label == AttackVector::Email).then(|| "attack_type:phishing".to_string())),
+        Classification::Target(label) => (label == Target::EmailAttack).then(|| "attack_type:phishing".to_string()),
+        Classification::Urgency(_) => None,
+    }
+}
+
+/// Lists which keyword triggered each of the record's classifications, or
+/// where a label no keyword backs came from.
+pub fn classify_with_provenance(record: &OTXRecord) -> Vec<ClassificationHit> {
+    let config = EnricherConfig { include_provenance: true, redact_tlp: false, ..config().clone() };
+    enrich_record_with(record, &config).hits
+}
+
+
+/// Flattened Classification Text
+
+/// Record field a piece of classification text was taken from.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TextSource {
+    Name,
+    Description,
+    /// Index into the record's tags.
+    Tag(usize),
+    /// Title or description of the indicator with this id.
+    Indicator(u128),
+    /// Role of the indicator with this id.
+    IndicatorRole(u128),
+}
+
+/// Which group of fields a classifier reads.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextScope {
+    /// Name, description and tags.
+    Pulse,
+    /// Indicator titles and descriptions.
+    Indicators,
+    /// Indicator roles.
+    IndicatorRoles,
+}
+
+impl TextSource {
+    pub fn scope(&self) -> TextScope {
+        match self {
+            TextSource::Name | TextSource::Description | TextSource::Tag(_) => TextScope::Pulse,
+            TextSource::Indicator(_) => TextScope::Indicators,
+            TextSource::IndicatorRole(_) => TextScope::IndicatorRoles,
+        }
+    }
+}
+
+const ATTACK_TYPE_SCOPE: &[TextScope] = &[TextScope::Pulse, TextScope::Indicators, TextScope::IndicatorRoles];
+const ATTACK_VECTOR_SCOPE: &[TextScope] = &[TextScope::Pulse, TextScope::Indicators];
+const URGENCY_SCOPE: &[TextScope] = &[TextScope::Pulse];
+const TARGET_SCOPE: &[TextScope] = &[TextScope::Pulse, TextScope::Indicators];
+
+/// Default cap on the combined text of one record that is classified.
+pub const DEFAULT_MAX_TEXT_BYTES: usize = 64 * 1024;
+
+/// Text of one record, built once and shared by every classifier. Each field
+/// is kept separately so keyword hits can be traced back to it. Fields are
+/// folded with `fold_for_matching`, so they keep their ASCII case and borrow
+/// from the record unless separators or other scripts need folding.
+pub struct FlattenedText<'a> {
+    pub record: &'a OTXRecord,
+    pub fields: Vec<(TextSource, std::borrow::Cow<'a, str>)>,
+    /// Whether the record's text exceeded the size cap and was cut short.
+    pub truncated: bool,
+}
+
+impl<'a> FlattenedText<'a> {
+    /// Flattens `record` under the configured `max_text_bytes` and
+    /// `max_indicators_scanned` caps.
+    pub fn new(record: &'a OTXRecord) -> Self {
+        Self::with_limits(record, config().max_text_bytes, config().max_indicators_scanned)
+    }
+
+    /// Flattens `record`, keeping at most `max_bytes` of raw text across all
+    /// fields in order. Fields past the cap are cut at a character boundary
+    /// before normalization, so oversized input is never copied whole.
+    pub fn with_limit(record: &'a OTXRecord, max_bytes: usize) -> Self {
+        Self::with_limits(record, max_bytes, None)
+    }
+
+    /// Like `with_limit`, also reading at most `max_indicators` indicators,
+    /// active ones first.
+    pub fn with_limits(record: &'a OTXRecord, max_bytes: usize, max_indicators: Option<usize>) -> Self {
+        let mut raw: Vec<(TextSource, std::borrow::Cow<'a, str>)> = vec![
+            (TextSource::Name, record.name.as_str().into()),
+            (TextSource::Description, record.description.as_str().into()),
+        ];
+        raw.extend(record.tags.iter().enumerate().map(|(index, tag)| {
+            (TextSource::Tag(index), tag.as_str().into())
+        }));
+        let mut indicators: Vec<&OTXIndicator> = record.indicators.iter().collect();
+        if let Some(max_indicators) = max_indicators {
+            indicators.sort_by_key(|ind| ind.is_active == 0);
+            indicators.truncate(max_indicators);
+        }
+        for ind in indicators {
+            let indicator_text = [ind.title.as_str(), ind.description.as_str()].join(" ");
+            raw.push((TextSource::Indicator(ind.id), indicator_text.into()));
+            if let Some(role) = &ind.role {
+                raw.push((TextSource::IndicatorRole(ind.id), role.as_str().into()));
+            }
         }
-    }).sum();
-    let flattened = all_text.join(" ").to_lowercase();
 
-    for (keyword, urgency_rec) in urgency_keywords().iter() {
-        if flattened.contains(keyword) {
-            match *urgency_rec {
-                Urgency::Critical | Urgency::Medium | Urgency::Low => {
-                    urgency_info.1 = *urgency_rec;
+        let mut fields = vec![];
+        let mut remaining = max_bytes;
+        let mut truncated = false;
+        for (source, text) in raw {
+            if text.len() > remaining {
+                truncated = true;
+                let kept = truncate_at_char_boundary(&text, remaining);
+                if !kept.is_empty() {
+                    fields.push((source, fold_for_matching(kept).into_owned().into()));
                 }
-                _ => {}
+                break;
             }
+            remaining -= text.len();
+            let folded = match text {
+                std::borrow::Cow::Borrowed(text) => fold_for_matching(text),
+                std::borrow::Cow::Owned(text) => fold_for_matching(&text).into_owned().into(),
+            };
+            fields.push((source, folded));
         }
+        FlattenedText { record, fields, truncated }
+    }
+
+    /// Texts of the fields that fall within `scope`.
+    pub fn texts(&self, scope: &[TextScope]) -> Vec<&str> {
+        self.fields
+            .iter()
+            .filter(|(source, _)| scope.contains(&source.scope()))
+            .map(|(_, text)| text.as_ref())
+            .collect()
     }
-    if tipper > 0 {
-        urgency_info.0 = Urgency::Hot
+}
+
+
+/// Lowercases `text` and folds hyphens, underscores and runs of whitespace
+/// into a single space, so "spear-phishing", "spear_phishing" and a phrase
+/// broken across lines all match the one keyword "spear phishing".
+/// Zero-width and control characters are dropped and the Turkish dotted and
+/// dotless I fold to a plain "i", so "PHİSHİNG" still reads as "phishing".
+pub fn normalize(text: &str) -> String {
+    fold(text, true)
+}
+
+/// Folds `text` like `normalize` but keeps ASCII letters in their case, for
+/// matchers that ignore ASCII case themselves. Text that needs no folding,
+/// such as plain ASCII words separated by single spaces, is borrowed rather
+/// than copied.
+pub fn fold_for_matching(text: &str) -> std::borrow::Cow<'_, str> {
+    let folded = text.bytes().all(|b| (b.is_ascii_graphic() && b != b'-' && b != b'_') || b == b' ')
+        && !text.starts_with(' ')
+        && !text.ends_with(' ')
+        && !text.contains("  ");
+    if folded {
+        text.into()
     } else {
-        urgency_info.0 = Urgency::Cold
+        fold(text, false).into()
     }
-    urgency_info 
 }
 
-fn classify_targets(record: &OTXRecord) -> Vec<Target> {
-    let mut targets: Vec<Target> = vec![];
-    let mut all_text = vec![
-        record.name.as_str(),
-        record.description.as_str(),
-    ];
-    all_text.extend(record.tags.iter().map(String::as_str));
-    all_text.extend(record.indicators.iter().flat_map(|ind: &OTXIndicator| vec![
-        ind.title.as_str(),
-        ind.description.as_str(),
-    ]));
-    let flattened = all_text.join(" ").to_lowercase();
-    
-    for (keyword, target) in target_keywords().iter() {
-        if flattened.contains(keyword) && !targets.contains(target) {
-            targets.push(*target);
+/// Shared by `normalize` and `fold_for_matching`; `lowercase_ascii` picks
+/// whether ASCII letters are lowercased too.
+fn fold(text: &str, lowercase_ascii: bool) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut pending_space = false;
+    for c in text.chars() {
+        if c == '-' || c == '_' || c.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if c.is_control() || is_zero_width(c) {
+            continue;
+        }
+        if pending_space && !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        pending_space = false;
+        match c {
+            'İ' | 'ı' => normalized.push('i'),
+            _ if c.is_ascii() && !lowercase_ascii => normalized.push(c),
+            _ => normalized.extend(c.to_lowercase()),
         }
     }
-    if targets.is_empty() {
-        vec![Target::Unknown]
+    normalized
+}
+
+/// The leading `max_bytes` of raw text across `fields`, cut at a character
+/// boundary, so extractors honor the same size cap as classification.
+fn capped<'t>(fields: impl Iterator<Item = &'t str>, max_bytes: usize) -> Vec<&'t str> {
+    let mut remaining = max_bytes;
+    let mut kept = vec![];
+    for field in fields {
+        if field.len() > remaining {
+            kept.push(truncate_at_char_boundary(field, remaining));
+            break;
+        }
+        remaining -= field.len();
+        kept.push(field);
+    }
+    kept
+}
+
+/// The longest prefix of `text` no longer than `max_bytes` that ends on a
+/// character boundary.
+fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Invisible formatting characters that can split a keyword without showing.
+fn is_zero_width(c: char) -> bool {
+    matches!(c, '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}')
+}
+
+/// Classification Functions
+
+/// Weight given to a named adversary backed by ATT&CK technique IDs when the
+/// text itself doesn't call the pulse an APT.
+const ATTRIBUTION_WEIGHT: f32 = 2.0;
+
+fn classify_attack_types(text: &FlattenedText, threshold: f32) -> Vec<Scored<AttackType>> {
+    let mut a_types: Vec<Scored<AttackType>> = vec![];
+    for label in text.record.malware_families.iter().filter_map(|family| malware_family_type(family)) {
+        if !a_types.iter().any(|scored| scored.label == label) {
+            a_types.push(Scored { label, confidence: TAG_CONFIDENCE });
+        }
+    }
+    for label in technique_ids(text.record).iter().filter_map(|id| technique_attack_type(id)) {
+        if !a_types.iter().any(|scored| scored.label == label) {
+            a_types.push(Scored { label, confidence: TAG_CONFIDENCE });
+        }
+    }
+    for scored in score_keyword_hits(attack_type_matcher(), text, ATTACK_TYPE_SCOPE, threshold) {
+        if !a_types.iter().any(|seen| seen.label == scored.label) {
+            a_types.push(scored);
+        }
+    }
+    if is_attributed(text.record) && !a_types.iter().any(|scored| scored.label == AttackType::APT) {
+        a_types.push(Scored { label: AttackType::APT, confidence: weight_confidence(ATTRIBUTION_WEIGHT) });
+    }
+    if let Some(label) = cve_attack_type(text) {
+        if !a_types.iter().any(|scored| scored.label == label) {
+            a_types.push(Scored { label, confidence: weight_confidence(CVE_WEIGHT) });
+        }
+    }
+    if has_typosquat_indicators(text.record) && !a_types.iter().any(|scored| scored.label == AttackType::Typosquatting) {
+        a_types.push(Scored { label: AttackType::Typosquatting, confidence: TAG_CONFIDENCE });
+    }
+    // A zero-day is the more specific kind of exploit.
+    if a_types.iter().any(|scored| scored.label == AttackType::ZeroDay) {
+        a_types.retain(|scored| scored.label != AttackType::Exploit);
+    }
+    a_types.sort_by_key(|scored| scored.label);
+    if a_types.is_empty() {
+        vec![Scored::unknown(AttackType::Unknown)]
     } else {
-        targets
+        a_types
     }
 }
 
-pub fn get_expiration(record: &OTXRecord) -> Option<String> {
-    let mut t_exp_date: Option<std::time::SystemTime> = None;
+/// Scores each attack type by its share of all attack type keyword hits in the
+/// record, so a record mentioning ransomware twice and phishing once scores
+/// them 0.67 and 0.33. Unlike `classify_attack_types` no threshold applies.
+pub fn classify_attack_types_scored(text: &FlattenedText) -> Vec<(AttackType, f32)> {
+    let hits: Vec<AttackType> = text.texts(ATTACK_TYPE_SCOPE)
+        .into_iter()
+        .flat_map(|field| attack_type_matcher().find_all(field))
+        .collect();
+    if hits.is_empty() {
+        return vec![(AttackType::Unknown, 0.0)];
+    }
 
-    for indicator in &record.indicators {
-        if let Some(expiration_str) = &indicator.expiration {
-            if let Ok(expiration_date) = parse_iso8601(expiration_str) {
-                
-                if t_exp_date.is_none() || expiration_date > t_exp_date.unwrap() {
-                    t_exp_date = Some(expiration_date);
-                }
+    let mut counts: Vec<(AttackType, usize)> = vec![];
+    for label in &hits {
+        match counts.iter_mut().find(|(seen, _)| seen == label) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*label, 1)),
+        }
+    }
+    counts.sort_by_key(|(label, _)| *label);
+    counts
+        .into_iter()
+        .map(|(label, count)| (label, ((count as f32 / hits.len() as f32) * 100.0).round() / 100.0))
+        .collect()
+}
+
+/// Weight given to CVE references when the text has no exploit keywords.
+const CVE_WEIGHT: f32 = 2.0;
+
+/// How many years before the pulse's last modification a CVE is still
+/// treated as fresh enough to be a zero-day.
+const ZERO_DAY_YEARS: u64 = 1;
+
+/// Matches a normalized CVE identifier such as "cve 2024 1234", capturing the
+/// year.
+fn cve_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)cve (\d{4}) \d{4,}").expect("cve pattern should compile"))
+}
+
+/// Years of every CVE referenced in the attack type fields, skipping negated
+/// mentions.
+fn cve_years(text: &FlattenedText) -> Vec<u64> {
+    text.texts(ATTACK_TYPE_SCOPE)
+        .into_iter()
+        .flat_map(|field| {
+            cve_pattern()
+                .captures_iter(field)
+                .filter(|caps| {
+                    let m = caps.get(0).expect("capture 0 is the whole match");
+                    is_whole_word(field, m.start(), m.end()) && !is_negated(field, m.start())
+                })
+                .filter_map(|caps| caps[1].parse().ok())
+                .collect::<Vec<u64>>()
+        })
+        .collect()
+}
+
+/// `ZeroDay` when the record cites a CVE from the year it was last modified
+/// or the year before, `Exploit` for older CVEs, and `None` without any.
+fn cve_attack_type(text: &FlattenedText) -> Option<AttackType> {
+    let years = cve_years(text);
+    if years.is_empty() {
+        return None;
+    }
+    let modified_year: Option<u64> = text.record.modified.get(..4).and_then(|year| year.parse().ok());
+    let recent = modified_year.is_some_and(|modified| {
+        years.iter().any(|year| modified.saturating_sub(*year) <= ZERO_DAY_YEARS)
+    });
+    Some(if recent { AttackType::ZeroDay } else { AttackType::Exploit })
+}
+
+/// Brands whose lookalike domains are a typosquatting signal.
+const TYPOSQUAT_BRANDS: &[&str] = &[
+    "google", "microsoft", "apple", "amazon", "paypal", "facebook", "instagram", "netflix",
+    "linkedin", "outlook", "office365", "github", "npmjs", "pypi", "dropbox", "docusign",
+];
+
+/// Most edits a domain label may be from a brand to count as a lookalike.
+const TYPOSQUAT_MAX_EDITS: usize = 2;
+
+/// Fewest lookalike domain indicators that suggest typosquatting.
+const TYPOSQUAT_MIN_INDICATORS: usize = 2;
+
+/// Whether at least `TYPOSQUAT_MIN_INDICATORS`, and at least half, of the
+/// record's domain and hostname indicators are lookalikes of a known brand.
+fn has_typosquat_indicators(record: &OTXRecord) -> bool {
+    let domains: Vec<&str> = record.indicators
+        .iter()
+        .filter(|ind| matches!(ind.type_.to_ascii_lowercase().as_str(), "domain" | "hostname"))
+        .map(|ind| ind.indicator.as_str())
+        .collect();
+    let lookalikes = domains.iter().filter(|domain| is_lookalike_domain(domain)).count();
+    lookalikes >= TYPOSQUAT_MIN_INDICATORS && lookalikes * 2 >= domains.len()
+}
+
+/// Whether the registered label of `domain`, "paypa1" in "login.paypa1.com",
+/// is a small edit away from a known brand without being the brand itself.
+/// Brands shorter than five letters only tolerate a single edit.
+fn is_lookalike_domain(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let mut labels = domain.rsplit('.');
+    let Some(label) = labels.nth(1) else {
+        return false;
+    };
+    TYPOSQUAT_BRANDS.iter().any(|brand| {
+        let max_edits = if brand.len() < 5 { 1 } else { TYPOSQUAT_MAX_EDITS };
+        let distance = edit_distance(label, brand);
+        distance > 0 && distance <= max_edits
+    })
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// CVE identifiers referenced anywhere in the record's name, description,
+/// tags, references or indicator titles and descriptions, written as
+/// "CVE-2023-4966" and deduplicated in order of first appearance. Lowercase
+/// and "CVE 2023-4966" spellings are accepted.
+pub fn extract_cves(record: &OTXRecord) -> Vec<String> {
+    cves_within(record, config().max_text_bytes)
+}
+
+/// `extract_cves` over at most `max_bytes` of the record's text.
+fn cves_within(record: &OTXRecord, max_bytes: usize) -> Vec<String> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| Regex::new(r"(?i)\bcve[- ](\d{4}-\d{4,7})\b").expect("cve pattern should compile"));
+
+    let fields = [record.name.as_str(), record.description.as_str()]
+        .into_iter()
+        .chain(record.tags.iter().map(String::as_str))
+        .chain(record.references.iter().map(String::as_str))
+        .chain(record.indicators.iter().flat_map(|ind| [ind.title.as_str(), ind.description.as_str()]));
+    let mut cves: Vec<String> = vec![];
+    for field in capped(fields, max_bytes) {
+        for cve in pattern.captures_iter(field).map(|caps| format!("CVE-{}", &caps[1])) {
+            if !cves.contains(&cve) {
+                cves.push(cve);
             }
         }
     }
-    t_exp_date
-        .map(|date| format_system_time(date))
+    cves
 }
 
-fn parse_iso8601(date_str: &str) -> std::result::Result<std::time::SystemTime, Box<dyn std::error::Error>> {
-    let parts: Vec<&str> = date_str.split(['T', '-', ':', '.']).collect();
-    if parts.len() < 6 {
-        return Err("Invalid date format".into());
-    }
-
-    let year: u64 = parts[0].parse().map_err(|e| format!("Failed to parse year: {}", e))?;
-    let month: u64 = parts[1].parse().map_err(|e| format!("Failed to parse month: {}", e))?;
-    let day: u64 = parts[2].parse().map_err(|e| format!("Failed to parse day: {}", e))?;
-    let hour: u64 = parts[3].parse().map_err(|e| format!("Failed to parse hour: {}", e))?;
-    let minute: u64 = parts[4].parse().map_err(|e| format!("Failed to parse minute: {}", e))?;
-    let second: u64 = parts[5].parse().map_err(|e| format!("Failed to parse second: {}", e))?;
-
-    let duration_since_epoch = std::time::Duration::new(
-        ((year - 1970) * 31_536_000)
-            + ((month - 1) * 2_592_000)
-            + ((day - 1) * 86_400) 
-            + (hour * 3_600) 
-            + (minute * 60) 
-            + second,
-        0,
-    );
+/// Canonical technique IDs ("T1566", "T1059.001") from the record's
+/// `attack_ids`, deduplicated. Entries are matched loosely, so "t1566 -
+/// phishing" yields "T1566"; entries without a technique ID are dropped.
+pub fn technique_ids(record: &OTXRecord) -> Vec<String> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| Regex::new(r"(?i)\bt\d{4}(?:\.\d{3})?\b").expect("technique pattern should compile"));
 
-    Ok(std::time::SystemTime::UNIX_EPOCH + duration_since_epoch)
+    let mut ids: Vec<String> = vec![];
+    for id in record.attack_ids.iter().filter_map(|raw| pattern.find(raw)).map(|m| m.as_str().to_uppercase()) {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
 }
 
-fn format_system_time(time: std::time::SystemTime) -> String {
-    let duration_since_epoch = time
-        .duration_since(std::time::SystemTime::UNIX_EPOCH)
-        .unwrap_or_default();
-    let seconds = duration_since_epoch.as_secs();
+/// The parent technique of a sub-technique ID, or the ID itself.
+fn parent_technique(id: &str) -> &str {
+    id.split_once('.').map_or(id, |(parent, _)| parent)
+}
 
-    let year = 1970 + seconds / 31_536_000;
-    let month = (seconds % 31_536_000) / 2_592_000 + 1;
-    let day = (seconds % 2_592_000) / 86_400 + 1;
-    let hour = (seconds % 86_400) / 3_600;
-    let minute = (seconds % 3_600) / 60;
-    let second = seconds % 60;
+/// Looks up every technique the record references. Sub-techniques missing
+/// from the table take their parent's name and tactic.
+pub fn mitre_techniques(record: &OTXRecord) -> Vec<MitreTechnique> {
+    let table = mitre_technique_table();
+    technique_ids(record)
+        .into_iter()
+        .map(|id| {
+            let known = table.get(id.as_str()).or_else(|| table.get(parent_technique(&id)));
+            MitreTechnique {
+                name: known.map(|(name, _)| name.to_string()),
+                tactic: known.map(|(_, tactic)| tactic.to_string()),
+                id,
+            }
+        })
+        .collect()
+}
 
-    format!(
-        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
-        year, month, day, hour, minute, second
-    )
+/// Distinct tactics of `techniques`, in order, skipping unknown techniques.
+pub fn mitre_tactics(techniques: &[MitreTechnique]) -> Vec<String> {
+    let mut tactics: Vec<String> = vec![];
+    for tactic in techniques.iter().filter_map(|technique| technique.tactic.as_ref()) {
+        if !tactics.contains(tactic) {
+            tactics.push(tactic.clone());
+        }
+    }
+    tactics
+}
+
+/// The attack type a technique or its parent technique implies, if any.
+fn technique_attack_type(id: &str) -> Option<AttackType> {
+    let map = technique_attack_types();
+    map.get(id).or_else(|| map.get(parent_technique(id))).copied()
 }
 
+/// The canonical name of a well-known threat actor, matching any alias in
+/// `threat_actor_aliases` regardless of case, spacing or hyphenation.
+pub fn known_threat_actor(adversary: &str) -> Option<&'static str> {
+    let squashed: String = normalize(adversary).chars().filter(|c| c.is_alphanumeric()).collect();
+    if squashed.is_empty() {
+        return None;
+    }
+    threat_actor_aliases()
+        .into_iter()
+        .find(|(alias, _)| alias.replace(' ', "") == squashed)
+        .map(|(_, canonical)| canonical)
+}
 
-/// Hashmap functions for synonymous cases
+/// Adversary values OTX authors use to say the adversary isn't known.
+const ADVERSARY_PLACEHOLDERS: &[&str] = &["unknown", "n/a", "na", "none", "-"];
 
-pub fn attack_type_keywords() -> HashMap<&'static str, AttackType> {
-    let mut m = HashMap::new();
+/// The pulse's adversary as written, trimmed, or `None` when it is empty or
+/// a placeholder such as "Unknown" or "N/A".
+pub fn adversary(record: &OTXRecord) -> Option<String> {
+    let adversary = record.adversary.trim();
+    if adversary.is_empty() || ADVERSARY_PLACEHOLDERS.contains(&adversary.to_lowercase().as_str()) {
+        return None;
+    }
+    Some(adversary.to_string())
+}
+
+/// The pulse's adversary under its canonical name, the raw name when it
+/// isn't a known group, or `None` when the pulse names no adversary.
+pub fn threat_actor(record: &OTXRecord) -> Option<String> {
+    let adversary = adversary(record)?;
+    Some(known_threat_actor(&adversary).map_or(adversary, str::to_string))
+}
+
+/// The highest CVSS base score stated in the record's name, description or
+/// references, either as a bare score ("CVSS 9.8", "CVSSv3 score: 7.5") or
+/// computed from a CVSS 3.x vector string. Scores outside 0.0..=10.0 are
+/// ignored.
+pub fn extract_cvss_score(record: &OTXRecord) -> Option<f32> {
+    cvss_score_within(record, config().max_text_bytes)
+}
 
-    m.insert("ransom", AttackType::Ransomware);
-    m.insert("ransomware", AttackType::Ransomware);
-    m.insert("locker", AttackType::Ransomware);
-    m.insert("cryptolocker", AttackType::Ransomware);
-    m.insert("encryptor", AttackType::Ransomware);
-    m.insert("crypto-malware", AttackType::Ransomware);
-
-    m.insert("malware", AttackType::Malware);
-    m.insert("virus", AttackType::Malware);
-    m.insert("worm", AttackType::Malware);
-    m.insert("adware", AttackType::Malware);
-    m.insert("rootkit", AttackType::Malware);
-    m.insert("keylogger", AttackType::Malware);
-
-    m.insert("ddos", AttackType::Ddos);
-    m.insert("dos", AttackType::Ddos);
-    m.insert("denial of service", AttackType::Ddos);
-    m.insert("distributed denial of service", AttackType::Ddos);
-    m.insert("flood attack", AttackType::Ddos);
-    m.insert("syn flood", AttackType::Ddos);
-    m.insert("amplification attack", AttackType::Ddos);
-
-    m.insert("botnet", AttackType::Botnet);
-    m.insert("bot network", AttackType::Botnet);
-    m.insert("zombie network", AttackType::Botnet);
-    m.insert("c&c", AttackType::Botnet);
-    m.insert("command and control", AttackType::Botnet);
-
-    m.insert("phish", AttackType::Phishing);
-    m.insert("phishing", AttackType::Phishing);
-    m.insert("spearphish", AttackType::Phishing);
-    m.insert("spear-phishing", AttackType::Phishing);
-    m.insert("whaling", AttackType::Phishing);
-    m.insert("credential harvesting", AttackType::Phishing);
-    m.insert("email scam", AttackType::Phishing);
-    m.insert("smishing", AttackType::Phishing);
-    m.insert("vishing", AttackType::Phishing);
-
-    m.insert("trojan", AttackType::Trojan);
-    m.insert("trojan horse", AttackType::Trojan);
-    m.insert("dropper", AttackType::Trojan);
-    m.insert("backdoor", AttackType::Trojan);
-    m.insert("infostealer", AttackType::Trojan);
-
-    m.insert("spyware", AttackType::Spyware);
-    m.insert("snoopware", AttackType::Spyware);
-    m.insert("tracking software", AttackType::Spyware);
-    m.insert("monitoring tool", AttackType::Spyware);
-
-    m.insert("brute force", AttackType::BruteForce);
-    m.insert("bruteforce", AttackType::BruteForce);
-    m.insert("credential stuffing", AttackType::BruteForce);
-    m.insert("password cracking", AttackType::BruteForce);
-    m.insert("dictionary attack", AttackType::BruteForce);
-
-    m.insert("sql injection", AttackType::SQLInjection);
-    m.insert("sqli", AttackType::SQLInjection);
-    m.insert("injection attack", AttackType::SQLInjection);
-    m.insert("database injection", AttackType::SQLInjection);
-    m.insert("blind sql", AttackType::SQLInjection);
-    m.insert("error-based injection", AttackType::SQLInjection);
-    m.insert("union-based injection", AttackType::SQLInjection);
+/// `extract_cvss_score` over at most `max_bytes` of the record's text.
+fn cvss_score_within(record: &OTXRecord, max_bytes: usize) -> Option<f32> {
+    static SCORE: OnceLock<Regex> = OnceLock::new();
+    static VECTOR: OnceLock<Regex> = OnceLock::new();
+    let score_pattern = SCORE.get_or_init(|| {
+        Regex::new(r"(?i)\bcvss(?:v[23](?:\.\d)?)?(?: base)?(?: score)?[\s:=]*(\d{1,3}(?:\.\d+)?)")
+            .expect("cvss score pattern should compile")
+    });
+    let vector_pattern = VECTOR.get_or_init(|| {
+        Regex::new(r"(?i)\bcvss:3\.[01]/[a-z]+:[a-z](?:/[a-z]+:[a-z])*").expect("cvss vector pattern should compile")
+    });
 
-    m
+    let fields = [record.name.as_str(), record.description.as_str()]
+        .into_iter()
+        .chain(record.references.iter().map(String::as_str));
+    let mut highest: Option<f32> = None;
+    for field in capped(fields, max_bytes) {
+        let scores = score_pattern
+            .captures_iter(field)
+            .filter(|caps| {
+                // "CVSS:3.1/AV:N/..." is a vector string, not a score of 3.1.
+                let end = caps.get(0).expect("capture 0 is the whole match").end();
+                !field[end..].starts_with('/')
+            })
+            .filter_map(|caps| caps[1].parse::<f32>().ok());
+        let vectors = vector_pattern.find_iter(field).filter_map(|m| cvss_vector_score(m.as_str()));
+        for score in scores.chain(vectors).filter(|score| (0.0..=10.0).contains(score)) {
+            highest = Some(highest.map_or(score, |high| high.max(score)));
+        }
+    }
+    highest
 }
 
-pub fn attack_vector_keywords() -> HashMap<&'static str, AttackVector> {
-    let mut m = HashMap::new();
+/// Base score of a CVSS 3.0 or 3.1 vector string such as
+/// "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H", per the specification's
+/// base equations. `None` when a base metric is missing or invalid.
+pub fn cvss_vector_score(vector: &str) -> Option<f32> {
+    let metrics: HashMap<String, String> = vector
+        .split('/')
+        .skip(1)
+        .filter_map(|metric| metric.split_once(':'))
+        .map(|(name, value)| (name.to_ascii_uppercase(), value.to_ascii_uppercase()))
+        .collect();
+    let metric = |name: &str| metrics.get(name).map(String::as_str);
 
-    m.insert("email", AttackVector::Email);
-    m.insert("phishing", AttackVector::Email);
-    m.insert("spearphish", AttackVector::Email);
-    m.insert("spoofing", AttackVector::Email);
-
-    m.insert("web", AttackVector::WebApplication);
-    m.insert("xss", AttackVector::WebApplication);
-    m.insert("cross-site scripting", AttackVector::WebApplication);
-    m.insert("sql injection", AttackVector::WebApplication);
-    m.insert("sqli", AttackVector::WebApplication);
-    m.insert("csrf", AttackVector::WebApplication);
-    m.insert("directory traversal", AttackVector::WebApplication);
-
-    m.insert("network", AttackVector::Network);
-    m.insert("ddos", AttackVector::Network);
-    m.insert("denial of service", AttackVector::Network);
-    m.insert("port scan", AttackVector::Network);
-    m.insert("mitm", AttackVector::Network);
-    m.insert("man in the middle", AttackVector::Network);
-
-    m.insert("cloud", AttackVector::CloudService);
-    m.insert("aws", AttackVector::CloudService);
-    m.insert("gcp", AttackVector::CloudService);
-    m.insert("azure", AttackVector::CloudService);
-    m.insert("bucket", AttackVector::CloudService);
-    m.insert("s3", AttackVector::CloudService);
-    m.insert("misconfig", AttackVector::CloudService);
-    m.insert("storage exposure", AttackVector::CloudService);
-
-    m.insert("supply chain", AttackVector::SupplyChain);
-    m.insert("dependency confusion", AttackVector::SupplyChain);
-    m.insert("software supply chain", AttackVector::SupplyChain);
-    m.insert("package hijack", AttackVector::SupplyChain);
-    m.insert("vendor compromise", AttackVector::SupplyChain);
+    let changed = match metric("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let attack_vector = match metric("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let attack_complexity = match metric("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let privileges = match (metric("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let interaction = match metric("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let impact_of = |name: &str| match metric(name)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let (c, i, a): (f64, f64, f64) = (impact_of("C")?, impact_of("I")?, impact_of("A")?);
 
-    m
+    let iss = 1.0 - (1.0 - c) * (1.0 - i) * (1.0 - a);
+    let impact = if changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15)
+    } else {
+        6.42 * iss
+    };
+    let exploitability = 8.22 * attack_vector * attack_complexity * privileges * interaction;
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let base = if changed { 1.08 * (impact + exploitability) } else { impact + exploitability };
+    Some(cvss_round_up(base.min(10.0)) as f32)
 }
 
-pub fn urgency_keywords() -> HashMap<&'static str, Urgency> {
-    let mut m = HashMap::new();
+/// CVSS 3.1 "Roundup": the smallest one-decimal number not below `value`,
+/// computed on integers to avoid floating point artifacts.
+fn cvss_round_up(value: f64) -> f64 {
+    let scaled = (value * 100_000.0).round() as u64;
+    if scaled.is_multiple_of(10_000) {
+        scaled as f64 / 100_000.0
+    } else {
+        (scaled / 10_000 + 1) as f64 / 10.0
+    }
+}
 
-    // Hot threats: immediate, active, breaking
-    m.insert("hot", Urgency::Hot);
-    m.insert("immediate", Urgency::Hot);
-    m.insert("active", Urgency::Hot);
-    m.insert("ongoing", Urgency::Hot);
-    m.insert("breaking", Urgency::Hot);
-
-    // Cold threats: old, inactive, stale
-    m.insert("cold", Urgency::Cold);
-    m.insert("stale", Urgency::Cold);
-    m.insert("archived", Urgency::Cold);
-    m.insert("historical", Urgency::Cold);
-    m.insert("retired", Urgency::Cold);
-    m.insert("inactive", Urgency::Cold);
-
-    // Critical threats: high severity, urgent, severe
-    m.insert("critical", Urgency::Critical);
-    m.insert("high", Urgency::Critical);
-    m.insert("severe", Urgency::Critical);
-    m.insert("urgent", Urgency::Critical);
-    m.insert("emergency", Urgency::Critical);
-
-    // Medium threats
-    m.insert("medium", Urgency::Medium);
-    m.insert("moderate", Urgency::Medium);
-    m.insert("average", Urgency::Medium);
-    m.insert("balanced", Urgency::Medium);
-
-    // Low threats: minor, low priority
-    m.insert("low", Urgency::Low);
-    m.insert("minor", Urgency::Low);
-    m.insert("negligible", Urgency::Low);
-    m.insert("low priority", Urgency::Low);
-    m.insert("minimal", Urgency::Low);
+/// The severity of a CVSS base score.
+fn cvss_severity(score: f32) -> Severity {
+    if score >= 7.0 {
+        Severity::Critical
+    } else if score >= 4.0 {
+        Severity::Medium
+    } else {
+        Severity::Low
+    }
+}
 
-    m
+/// Whether the pulse names an adversary and maps its activity to ATT&CK
+/// techniques, which together point at a tracked, persistent actor.
+fn is_attributed(record: &OTXRecord) -> bool {
+    adversary(record).is_some() && !record.attack_ids.is_empty()
 }
 
-pub fn target_keywords() -> HashMap<&'static str, Target> {
-    let mut m = HashMap::new();
+fn classify_attack_vectors(text: &FlattenedText, threshold: f32) -> Vec<Scored<AttackVector>> {
+    let mut a_vectors = score_keyword_hits(attack_vector_matcher(), text, ATTACK_VECTOR_SCOPE, threshold);
+    for scored in indicator_type_vectors(text.record) {
+        if !a_vectors.iter().any(|seen| seen.label == scored.label) {
+            a_vectors.push(scored);
+        }
+    }
+    a_vectors.sort_by_key(|scored| scored.label);
+    if a_vectors.is_empty() {
+        vec![Scored::unknown(AttackVector::Unknown)]
+    } else {
+        a_vectors
+    }
+}
 
-    // Web Applications
-    m.insert("webapp", Target::WebApp);
-    m.insert("web app", Target::WebApp);
-    m.insert("website", Target::WebApp);
-    m.insert("web application", Target::WebApp);
-    m.insert("web portal", Target::WebApp);
-    m.insert("online service", Target::WebApp);
-    m.insert("web service", Target::WebApp);
-
-    // Infrastructure
-    m.insert("infrastructure", Target::Infrastructure);
-    m.insert("server", Target::Infrastructure);
-    m.insert("servers", Target::Infrastructure);
-    m.insert("datacenter", Target::Infrastructure);
-    m.insert("data center", Target::Infrastructure);
-    m.insert("network infra", Target::Infrastructure);
-    m.insert("cloud infrastructure", Target::Infrastructure);
-    m.insert("system", Target::Infrastructure);
-    m.insert("backend", Target::Infrastructure);
-
-    // API Abuse
-    m.insert("api abuse", Target::ApiAbuse);
-    m.insert("api exploitation", Target::ApiAbuse);
-    m.insert("api attack", Target::ApiAbuse);
-    m.insert("api misuse", Target::ApiAbuse);
-    m.insert("rest api", Target::ApiAbuse);
-    m.insert("graphql api", Target::ApiAbuse);
-    m.insert("api endpoint", Target::ApiAbuse);
-
-    // IoT Devices
-    m.insert("iot", Target::IotDevices);
-    m.insert("device", Target::IotDevices);
-    m.insert("smart devices", Target::IotDevices);
-    m.insert("smart home", Target::IotDevices);
-    m.insert("embedded systems", Target::IotDevices);
-    m.insert("industrial control systems", Target::IotDevices);
-    m.insert("ics", Target::IotDevices);
-    m.insert("plc", Target::IotDevices);
-    m.insert("smart tv", Target::IotDevices);
-    m.insert("iot network", Target::IotDevices);
-
-    // User Focused
-    m.insert("user", Target::UserFocused);
-    m.insert("users", Target::UserFocused);
-    m.insert("human", Target::UserFocused);
-    m.insert("human target", Target::UserFocused);
-    m.insert("social engineering", Target::UserFocused);
-    m.insert("account takeover", Target::UserFocused);
-    m.insert("identity theft", Target::UserFocused);
-    m.insert("credential theft", Target::UserFocused);
-    m.insert("login brute force", Target::UserFocused);
-    m.insert("phishing scam", Target::UserFocused);
-
-    // Email Attacks
-    m.insert("email", Target::EmailAttack);
-    m.insert("email attack", Target::EmailAttack);
-    m.insert("email phishing", Target::EmailAttack);
-    m.insert("email spoofing", Target::EmailAttack);
-    m.insert("spam email", Target::EmailAttack);
-    m.insert("malicious email", Target::EmailAttack);
-    m.insert("email fraud", Target::EmailAttack);
-    m.insert("spearphishing", Target::EmailAttack);
-    m.insert("mail scam", Target::EmailAttack);
-    m.insert("mail fraud", Target::EmailAttack);
+/// Counts every keyword hit behind each classified vector. Several keywords
+/// for one vector, such as "sql injection" and "sqli", still yield a single
+/// vector, but all of their hits are counted.
+pub fn count_attack_vector_matches(text: &FlattenedText, vectors: &[Scored<AttackVector>]) -> Vec<(AttackVector, usize)> {
+    let mut counts: Vec<(AttackVector, usize)> = vectors
+        .iter()
+        .filter(|vector| vector.label != AttackVector::Unknown)
+        .map(|vector| (vector.label, 0))
+        .collect();
+    let hits = text.texts(ATTACK_VECTOR_SCOPE).into_iter().flat_map(|field| attack_vector_matcher().find_all(field));
+    for label in hits {
+        if let Some((_, count)) = counts.iter_mut().find(|(seen, _)| *seen == label) {
+            *count += 1;
+        }
+    }
+    counts
+}
 
-    m
-}
\ No newline at end of file
+/// The vector with the most keyword hits, ties going to the vector declared
+/// first in `AttackVector`. `Unknown` when no vector was classified.
+pub fn dominant_vector(matches: &[(AttackVector, usize)]) -> AttackVector {
+    matches
+        .iter()
+        .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))
+        .map_or(AttackVector::Unknown, |(vector, _)| *vector)
+}
+
+/// The vector an indicator of the given OTX type is delivered over, if any.
+/// File hashes and similar artifacts say nothing about delivery.
+pub fn indicator_type_vector(type_: &str) -> Option<AttackVector> {
+    match type_.to_ascii_lowercase().as_str() {
+        "url" | "uri" | "hostname" | "domain" => Some(AttackVector::WebApplication),
+        "email" => Some(AttackVector::Email),
+        "ipv4" | "ipv6" | "cidr" => Some(AttackVector::Network),
+        _ => None,
+    }
+}
+
+/// Vectors implied by the record's indicator types, each scored as one
+/// indicator-weight hit per indicator. Keyword-derived vectors take
+/// precedence, so these only add vectors the text didn't mention.
+fn indicator_type_vectors(record: &OTXRecord) -> Vec<Scored<AttackVector>> {
+    let mut counts: Vec<(AttackVector, f32)> = vec![];
+    for vector in record.indicators.iter().filter_map(|ind| indicator_type_vector(&ind.type_)) {
+        match counts.iter_mut().find(|(seen, _)| *seen == vector) {
+            Some((_, count)) => *count += 1.0,
+            None => counts.push((vector, 1.0)),
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(label, weight)| {
+            Scored { label, confidence: weight_confidence(weight) }
+        })
+        .collect()
+}
+
+fn classify_urgency(text: &FlattenedText, config: &EnricherConfig) -> UrgencyAssessment {
+    // With no indicators and no urgency cue there is nothing to assess.
+    let baseline = if text.record.indicators.is_empty() { Severity::Unknown } else { Severity::Low };
+    let mut urgency_info = UrgencyAssessment { temperature: Temperature::Unknown, severity: baseline };
+
+    // The most severe keyword wins. Without any, a published CVSS score
+    // decides, and regex rules only imply a baseline severity.
+    let (patterned, keyworded): (Vec<KeywordHit<Urgency>>, Vec<KeywordHit<Urgency>>) = text.texts(URGENCY_SCOPE)
+        .into_iter()
+        .flat_map(|field| urgency_matcher().hits(field))
+        .partition(|hit| hit.pattern);
+    let keyword_severity = highest_severity(keyworded.iter().map(|hit| hit.label));
+    let pattern_severity = highest_severity(patterned.iter().map(|hit| hit.label));
+    if let Some(severity) = keyword_severity
+        .or_else(|| cvss_score_within(text.record, config.max_text_bytes).map(cvss_severity))
+        .or(pattern_severity)
+    {
+        urgency_info.severity = severity;
+    }
+    // A referenced CVE means a known vulnerability is in play.
+    if urgency_info.severity <= Severity::Low && !cves_within(text.record, config.max_text_bytes).is_empty() {
+        urgency_info.severity = Severity::Medium;
+    }
+    // A known APT group behind the pulse makes it one level more severe.
+    if known_threat_actor(&text.record.adversary).is_some() {
+        urgency_info.severity = urgency_info.severity.escalate();
+    }
+    // Sharing restrictions signal how serious the author considers the pulse.
+    urgency_info.severity = match text.record.tlp {
+        Tlp::Red => Severity::Critical,
+        Tlp::Amber | Tlp::AmberStrict => urgency_info.severity.escalate(),
+        _ => urgency_info.severity,
+    };
+    // A pulse without indicators has no activity to measure.
+    urgency_info.temperature = match is_hot(text.record, std::time::SystemTime::now(), config) {
+        Some(true) => Temperature::Hot,
+        Some(false) => Temperature::Cold,
+        None => Temperature::Unknown,
+    };
+    urgency_info 
+}
+
+/// Default for how recently indicators must have been created for a pulse
+/// to be hot.
+pub const DEFAULT_HOT_WINDOW_DAYS: u64 = 30;
+
+/// Default share of indicators flagged active that a hot pulse must exceed.
+pub const DEFAULT_HOT_ACTIVE_RATIO: f32 = 0.5;
+
+/// Fewest indicators a pulse needs to be hot, so a pulse with one or two
+/// indicators doesn't flip between hot and cold as each is flagged.
+pub const MIN_HOT_INDICATORS: usize = 3;
+
+/// Default margin of active over inactive indicators a hot pulse needs. Any
+/// pulse whose active share exceeds the default ratio already clears it.
+pub const DEFAULT_HOT_THRESHOLD: i64 = 1;
+
+/// Default fewest active indicators a hot pulse needs. Any pulse with
+/// `MIN_HOT_INDICATORS` indicators and a majority active already clears it.
+pub const DEFAULT_MIN_ACTIVE_INDICATORS: usize = 2;
+
+/// Share of a pulse's dated indicators that must fall within the hot window.
+const HOT_RECENT_SHARE: f32 = 0.25;
+
+/// Whether the pulse is hot at `now`. It must have at least
+/// `MIN_HOT_INDICATORS` indicators, the share flagged active must exceed
+/// `hot_active_ratio`, at least `min_active_indicators` must be active and
+/// outnumber the inactive ones by `hot_threshold`, and at least `HOT_RECENT_SHARE` of those with a
+/// parseable `created` date must have been created within `hot_window_days`.
+/// When no indicator carries a date, the pulse's `modified` date stands in
+/// for all of them, so indicators still flagged active but all created long
+/// ago are cold. `None` for a pulse with no indicators at all.
+pub fn is_hot(record: &OTXRecord, now: std::time::SystemTime, config: &EnricherConfig) -> Option<bool> {
+    let total = record.indicators.len();
+    if total == 0 {
+        return None;
+    }
+    let active = record.indicators.iter().filter(|ind| ind.is_active == 1).count();
+    let tipper = active as i64 - (total - active) as i64;
+    if total < MIN_HOT_INDICATORS
+        || active as f32 / total as f32 <= config.hot_active_ratio
+        || active < config.min_active_indicators
+        || tipper < config.hot_threshold
+    {
+        return Some(false);
+    }
+
+    let window = std::time::Duration::from_secs(config.hot_window_days.saturating_mul(86_400));
+
+    let is_recent = |date: std::time::SystemTime| {
+        now.duration_since(date).map_or(true, |age| age <= window)
+    };
+    let dates: Vec<std::time::SystemTime> = record.indicators
+        .iter()
+        .filter_map(|ind| parse_iso8601(&ind.created).ok())
+        .collect();
+    if dates.is_empty() {
+        return Some(parse_iso8601(&record.modified).is_ok_and(is_recent));
+    }
+    let recent = dates.iter().filter(|date| is_recent(**date)).count();
+    Some(recent as f32 / dates.len() as f32 >= HOT_RECENT_SHARE)
+}
+
+/// The most severe severity named among `labels`, ignoring hot/cold.
+fn highest_severity(labels: impl Iterator<Item = Urgency>) -> Option<Severity> {
+    labels
+        .filter_map(Urgency::severity)
+        .max()
+}
+
+/// Single sortable urgency from 0 to 100, the sum of three parts:
+///
+/// - severity, from the `urgency` that `classify_urgency` assessed for the
+///   record: Critical 50, Medium 30, Low 10, Unknown 0;
+/// - activity, up to 30, scaled by the share of indicators flagged active,
+///   the same flags `is_hot` weighs; 0 with no indicators;
+/// - volume, 1 per indicator up to 20.
+pub fn classify_urgency_score(record: &OTXRecord, urgency: UrgencyAssessment) -> u8 {
+    let severity: u32 = match urgency.severity {
+        Severity::Critical => 50,
+        Severity::Medium => 30,
+        Severity::Low => 10,
+        Severity::Unknown => 0,
+    };
+    let total = record.indicators.len() as u32;
+    let active = record.indicators.iter().filter(|ind| ind.is_active == 1).count() as u32;
+    let activity = (30 * active).checked_div(total).unwrap_or(0);
+    let volume = total.min(20);
+    (severity + activity + volume) as u8
+}
+
+/// Single sortable threat score from 0 to 100 for an enriched record and the
+/// pulse it was built from, the sum of:
+///
+/// - severity, up to 35: Critical 35, Medium 20, Low 5, Unknown 0;
+/// - temperature, 15 when hot;
+/// - active indicators, 1 each up to 20;
+/// - a named adversary, 10;
+/// - targeted countries, 2 each up to 10;
+/// - one or more referenced CVEs, 10.
+pub fn compute_threat_score(record: &OTXRecord, enriched: &EnrichedThreatRecord) -> u8 {
+    let severity: usize = match enriched.urgency.severity {
+        Severity::Critical => 35,
+        Severity::Medium => 20,
+        Severity::Low => 5,
+        Severity::Unknown => 0,
+    };
+    let temperature = match enriched.urgency.temperature {
+        Temperature::Hot => 15,
+        Temperature::Cold | Temperature::Unknown => 0,
+    };
+    let indicators = enriched.active_indicator_count.min(20);
+    let adversary = if adversary(record).is_some() { 10 } else { 0 };
+    let countries = 2 * record.targeted_countries.len().min(5);
+    let cves = if enriched.cve_ids.is_empty() { 0 } else { 10 };
+    (severity + temperature + indicators + adversary + countries + cves) as u8
+}
+
+/// Active indicators that make an otherwise quiet pulse `Medium`.
+const MEDIUM_SEVERITY_INDICATORS: usize = 10;
+
+/// A single severity label for dashboards, see `severity_label` for the rules.
+pub fn overall_severity(record: &OTXRecord) -> &'static str {
+    let text = FlattenedText::new(record);
+    let (active, _) = count_indicators(record, std::time::SystemTime::now());
+    let attack_types = classify_attack_types(&text, config().classification_threshold);
+    severity_label(classify_urgency(&text, config()), active, &attack_types)
+}
+
+/// The first rule that holds decides the label:
+/// - "Critical": ransomware or an APT with at least one active indicator, or
+///   critical severity on a hot pulse;
+/// - "High": critical severity, a hot pulse, or ransomware or an APT without
+///   active indicators;
+/// - "Medium": medium severity, or `MEDIUM_SEVERITY_INDICATORS` or more
+///   active indicators;
+/// - "Low": low severity or any active indicator;
+/// - "Info": anything else, such as a pulse with no indicators at all.
+fn severity_label(urgency: UrgencyAssessment, active: usize, attack_types: &[Scored<AttackType>]) -> &'static str {
+    let high_impact = attack_types
+        .iter()
+        .any(|scored| matches!(scored.label, AttackType::Ransomware | AttackType::APT));
+    let hot = urgency.temperature == Temperature::Hot;
+    match urgency.severity {
+        _ if high_impact && active > 0 => "Critical",
+        Severity::Critical if hot => "Critical",
+        Severity::Critical => "High",
+        _ if hot || high_impact => "High",
+        Severity::Medium => "Medium",
+        _ if active >= MEDIUM_SEVERITY_INDICATORS => "Medium",
+        Severity::Low => "Low",
+        _ if active > 0 => "Low",
+        _ => "Info",
+    }
+}
+
+/// Drops `unknown` from `v` when anything else is in it, so a placeholder
+/// never sits beside a real classification. A lone `unknown` is kept.
+pub fn prune_unknown<T: PartialEq>(v: Vec<T>, unknown: T) -> Vec<T> {
+    if v.iter().all(|item| *item == unknown) {
+        return v;
+    }
+    v.into_iter().filter(|item| *item != unknown).collect()
+}
+
+/// Phishing arrives by email, so a phishing pulse always carries the `Email`
+/// vector and the `EmailAttack` target, at the confidence of its `Phishing`
+/// label when they weren't found on their own. Either replaces an `Unknown`
+/// placeholder.
+fn reconcile_phishing(
+    attack_types: &[Scored<AttackType>],
+    attack_vectors: &mut Vec<Scored<AttackVector>>,
+    targets: &mut Vec<Scored<Target>>,
+) {
+    let Some(phishing) = attack_types.iter().find(|scored| scored.label == AttackType::Phishing) else {
+        return;
+    };
+    if !attack_vectors.iter().any(|scored| scored.label == AttackVector::Email) {
+        attack_vectors.retain(|scored| scored.label != AttackVector::Unknown);
+        attack_vectors.push(Scored { label: AttackVector::Email, confidence: phishing.confidence });
+        attack_vectors.sort_by_key(|scored| scored.label);
+    }
+    if !targets.iter().any(|scored| scored.label == Target::EmailAttack) {
+        targets.retain(|scored| scored.label != Target::Unknown);
+        targets.push(Scored { label: Target::EmailAttack, confidence: phishing.confidence });
+        targets.sort_by_key(|scored| scored.label);
+    }
+}
+
+fn classify_targets(text: &FlattenedText, threshold: f32) -> Vec<Scored<Target>> {
+    let mut targets = score_keyword_hits(target_matcher(), text, TARGET_SCOPE, threshold);
+    targets.sort_by_key(|scored| scored.label);
+    if targets.is_empty() {
+        vec![Scored::unknown(Target::Unknown)]
+    } else {
+        targets
+    }
+}
+
+/// Sectors from the pulse's industries and from sector keywords in its name,
+/// description and tags, without duplicates and in declaration order.
+/// Industries that map to no sector add `Sector::Other` and are returned as
+/// written alongside.
+fn classify_sectors(text: &FlattenedText) -> (Vec<Sector>, Vec<String>) {
+    let mut sectors: BTreeSet<Sector> = classify_industries(text.record).into_iter().collect();
+    let raw_industries = text.record.industries
+        .iter()
+        .filter(|industry| industry_sectors(industry).is_empty())
+        .cloned()
+        .collect();
+    sectors.extend(text.texts(&[TextScope::Pulse]).into_iter().flat_map(|field| sector_matcher().find_all(field)));
+    (sectors.into_iter().collect(), raw_industries)
+}
+
+/// Industries named by the pulse's `industries` field alone, without
+/// duplicates and in declaration order. Industries that map to no sector are
+/// `Industry::Other`.
+pub fn classify_industries(record: &OTXRecord) -> Vec<Industry> {
+    let industries: BTreeSet<Industry> = record.industries
+        .iter()
+        .flat_map(|industry| {
+            let sectors = industry_sectors(industry);
+            if sectors.is_empty() { vec![Industry::Other] } else { sectors }
+        })
+        .collect();
+    industries.into_iter().collect()
+}
+
+/// Sectors one free-form industry names: the whole string as a known
+/// industry, or else every sector keyword within it, so "Healthcare and
+/// Banking" names two. Empty when nothing matches.
+fn industry_sectors(industry: &str) -> Vec<Sector> {
+    match sector_for_industry(industry) {
+        Some(sector) => vec![sector],
+        None => sector_matcher().find(&[&normalize(industry)]),
+    }
+}
+
+/// Platforms named by OS keywords in the record's text or implied by the file
+/// names of its file hash indicators, without duplicates and in declaration
+/// order. `Unknown` when nothing points at a platform.
+pub fn classify_os_targets(text: &FlattenedText) -> Vec<OsTarget> {
+    let keyword_hits = text.texts(TARGET_SCOPE).into_iter().flat_map(|field| os_target_matcher().find_all(field));
+    let os_targets: BTreeSet<OsTarget> = keyword_hits
+        .chain(text.record.indicators.iter().filter_map(indicator_os_target))
+        .collect();
+    if os_targets.is_empty() {
+        vec![OsTarget::Unknown]
+    } else {
+        os_targets.into_iter().collect()
+    }
+}
+
+/// The platform of a file hash indicator whose title or content names the
+/// file, as in "payload.exe".
+fn indicator_os_target(indicator: &OTXIndicator) -> Option<OsTarget> {
+    if !indicator.type_.to_ascii_lowercase().starts_with("filehash") {
+        return None;
+    }
+    [&indicator.title, &indicator.content]
+        .into_iter()
+        .flat_map(|field| field.split_whitespace())
+        .find_map(|word| {
+            let (_, extension) = word.rsplit_once('.')?;
+            match extension.to_ascii_lowercase().as_str() {
+                "exe" | "dll" | "msi" | "scr" | "ps1" => Some(OsTarget::Windows),
+                "elf" | "so" => Some(OsTarget::Linux),
+                "dmg" | "pkg" => Some(OsTarget::MacOS),
+                "apk" => Some(OsTarget::Android),
+                "ipa" => Some(OsTarget::Ios),
+                _ => None,
+            }
+        })
+}
+
+/// Maps a free-form industry such as "Financial Services" or "Hospitals" to
+/// its sector, trying the singular when the plural isn't listed.
+pub fn sector_for_industry(industry: &str) -> Option<Sector> {
+    let industry = normalize(industry);
+    let keywords = sector_keywords();
+    keywords
+        .get(industry.as_str())
+        .or_else(|| industry.strip_suffix('s').and_then(|singular| keywords.get(singular)))
+        .copied()
+}
+
+/// How much a single keyword hit counts towards a confidence score, by the
+/// field it was found in. Curated fields outweigh indicator prose.
+fn source_weight(source: &TextSource) -> f32 {
+    match source {
+        TextSource::Name | TextSource::Tag(_) => 3.0,
+        TextSource::Description => 2.0,
+        TextSource::Indicator(_) | TextSource::IndicatorRole(_) => 1.0,
+    }
+}
+
+/// Extra weight for every distinct keyword beyond the first that backs a label.
+const DISTINCT_KEYWORD_BONUS: f32 = 2.0;
+
+/// Weight at which a label reaches a confidence of 0.5.
+const CONFIDENCE_MIDPOINT: f32 = 4.0;
+
+/// Squashes an evidence weight into a confidence in 0.0..1.0, rounded to two
+/// decimals.
+fn weight_confidence(weight: f32) -> f32 {
+    let confidence = weight / (weight + CONFIDENCE_MIDPOINT);
+    (confidence * 100.0).round() / 100.0
+}
+
+/// Total keyword weight a label needs before it is emitted. A single strong
+/// keyword clears it, a single weak keyword does not.
+pub const DEFAULT_CLASSIFICATION_THRESHOLD: f32 = 0.5;
+
+/// Confidence given to a label read straight off a curated tag.
+pub const TAG_CONFIDENCE: f32 = 1.0;
+
+/// The label a tag names outright: the whole tag is a keyword that clears
+/// `threshold` on its own, or a tag alias. `None` for any other field.
+fn exact_tag_label<T: Copy>(matcher: &KeywordMatcher<T>, source: &TextSource, field: &str, threshold: f32) -> Option<T> {
+    if !matches!(source, TextSource::Tag(_)) {
+        return None;
+    }
+    matcher
+        .tag_label(field)
+        .filter(|(_, weight)| *weight > threshold)
+        .map(|(label, _)| label)
+}
+
+/// Scores every label the matcher finds in `scope`. Labels named outright by
+/// a tag come first with `TAG_CONFIDENCE`; the remaining labels are scored
+/// from free text, dropping those whose distinct keywords weigh no more than
+/// `threshold` in total. Each hit adds its keyword weight scaled by its source
+/// weight, each additional distinct keyword adds a bonus, and the total is
+/// squashed into 0.0..1.0 so more and broader evidence approaches 1.0.
+fn score_keyword_hits<T: Copy + PartialEq>(
+    matcher: &KeywordMatcher<T>,
+    text: &FlattenedText,
+    scope: &[TextScope],
+    threshold: f32,
+) -> Vec<Scored<T>> {
+    let fields: Vec<&(TextSource, std::borrow::Cow<str>)> = text.fields.iter().filter(|(source, _)| scope.contains(&source.scope())).collect();
+    let mut tagged: Vec<Scored<T>> = vec![];
+    for (source, field) in &fields {
+        if let Some(label) = exact_tag_label(matcher, source, field, threshold) {
+            if !tagged.iter().any(|seen| seen.label == label) {
+                tagged.push(Scored { label, confidence: TAG_CONFIDENCE });
+            }
+        }
+    }
+
+    let mut tallies: Vec<(T, f32, Vec<KeywordHit<T>>)> = vec![];
+    for (source, field) in fields {
+        for hit in matcher.hits(field) {
+            if tagged.iter().any(|seen| seen.label == hit.label) {
+                continue;
+            }
+            let index = match tallies.iter().position(|(seen, _, _)| *seen == hit.label) {
+                Some(index) => index,
+                None => {
+                    tallies.push((hit.label, 0.0, vec![]));
+                    tallies.len() - 1
+                }
+            };
+            let tally = &mut tallies[index];
+            tally.1 += hit.weight * source_weight(source);
+            if !tally.2.iter().any(|seen| seen.keyword == hit.keyword) {
+                tally.2.push(hit);
+            }
+        }
+    }
+    let scored = tallies
+        .into_iter()
+        .filter(|(_, _, keywords)| keywords.iter().map(|hit| hit.weight).sum::<f32>() > threshold)
+        .map(|(label, weight, keywords)| {
+            let weight = weight + DISTINCT_KEYWORD_BONUS * (keywords.len() - 1) as f32;
+            Scored { label, confidence: weight_confidence(weight) }
+        });
+    tagged.into_iter().chain(scored).collect()
+}
+
+/// Lists every keyword hit the matchers find in the record, with the field
+/// each hit came from, whether or not its label made it into the record's
+/// classifications. `threshold` decides which tags name a label outright.
+pub fn collect_evidence(text: &FlattenedText, threshold: f32) -> Vec<Evidence> {
+    let mut evidence: Vec<Evidence> = vec![];
+    for (source, field) in &text.fields {
+        let scope = source.scope();
+        let mut hits: Vec<Evidence> = vec![];
+        if ATTACK_TYPE_SCOPE.contains(&scope) {
+            hits.extend(field_evidence(attack_type_matcher(), source, field, threshold, Classification::AttackType));
+        }
+        if ATTACK_VECTOR_SCOPE.contains(&scope) {
+            hits.extend(field_evidence(attack_vector_matcher(), source, field, threshold, Classification::AttackVector));
+        }
+        if URGENCY_SCOPE.contains(&scope) {
+            hits.extend(field_evidence(urgency_matcher(), source, field, threshold, Classification::Urgency));
+        }
+        if TARGET_SCOPE.contains(&scope) {
+            hits.extend(field_evidence(target_matcher(), source, field, threshold, Classification::Target));
+        }
+        for hit in hits {
+            if !evidence.contains(&hit) {
+                evidence.push(hit);
+            }
+        }
+    }
+    evidence
+}
+
+/// The keyword hits behind `enriched`'s classifications: those from
+/// `collect_evidence` whose label it kept, dropping labels below the
+/// threshold and those `prune_unknown` or the urgency assessment overrode.
+fn classified_evidence(text: &FlattenedText, threshold: f32, enriched: &EnrichedThreatRecord) -> Vec<Evidence> {
+    collect_evidence(text, threshold)
+        .into_iter()
+        .filter(|evidence| is_classified(evidence.classification, enriched))
+        .collect()
+}
+
+/// Whether `classification` is among `enriched`'s labels, or for urgency
+/// cues names its assessed severity or temperature.
+fn is_classified(classification: Classification, enriched: &EnrichedThreatRecord) -> bool {
+    match classification {
+        Classification::AttackType(label) => enriched.attack_types.iter().any(|scored| scored.label == label),
+        Classification::AttackVector(label) => enriched.attack_vectors.iter().any(|scored| scored.label == label),
+        Classification::Target(label) => enriched.targets.iter().any(|scored| scored.label == label),
+        Classification::Urgency(label) => {
+            label.severity() == Some(enriched.urgency.severity) || label.temperature() == Some(enriched.urgency.temperature)
+        }
+    }
+}
+
+/// Evidence from one field for one matcher: the exact tag match if the field
+/// is a tag that matches whole, otherwise every keyword hit within it.
+fn field_evidence<T: Copy>(
+    matcher: &KeywordMatcher<T>,
+    source: &TextSource,
+    field: &str,
+    threshold: f32,
+    classification: fn(T) -> Classification,
+) -> Vec<Evidence> {
+    if let Some(label) = exact_tag_label(matcher, source, field, threshold) {
+        return vec![Evidence { classification: classification(label), keyword: field.to_ascii_lowercase(), source: *source, exact_tag: true }];
+    }
+    matcher
+        .hits(field)
+        .into_iter()
+        .map(|hit| Evidence {
+            classification: classification(hit.label),
+            keyword: hit.keyword.to_string(),
+            source: *source,
+            exact_tag: false,
+        })
+        .collect()
+}
+
+/// Typed IOCs for the record's active indicators, in indicator order.
+pub fn extract_iocs(record: &OTXRecord) -> Vec<Ioc> {
+    record
+        .indicators
+        .iter()
+        .filter(|ind| ind.is_active != 0)
+        .map(|ind| Ioc { value: ind.indicator.clone(), ioc_type: IocType::from_otx_type(&ind.type_) })
+        .collect()
+}
+
+/// Default cap on the references one record carries.
+pub const DEFAULT_MAX_REFERENCES: usize = 20;
+
+/// The record's distinct http and https references, trimmed, in order and at
+/// most `max` of them, along with how many were dropped as malformed.
+pub fn extract_references(record: &OTXRecord, max: usize) -> (Vec<String>, usize) {
+    let mut references: Vec<String> = vec![];
+    let mut dropped = 0;
+    for reference in record.references.iter().map(|reference| reference.trim()) {
+        if !is_http_url(reference) {
+            dropped += 1;
+        } else if references.len() < max && !references.iter().any(|kept| kept == reference) {
+            references.push(reference.to_string());
+        }
+    }
+    (references, dropped)
+}
+
+/// Whether `text` is a single http or https URL with a host.
+fn is_http_url(text: &str) -> bool {
+    let lowercase = text.to_ascii_lowercase();
+    let Some(rest) = lowercase.strip_prefix("https://").or_else(|| lowercase.strip_prefix("http://")) else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    !host.is_empty() && !text.contains(char::is_whitespace)
+}
+
+pub fn get_expiration(record: &OTXRecord) -> Option<String> {
+    parsed_expirations(record).0
+        .into_iter()
+        .max()
+        .map(format_system_time)
+}
+
+pub fn get_earliest_expiration(record: &OTXRecord) -> Option<String> {
+    parsed_expirations(record).0
+        .into_iter()
+        .min()
+        .map(format_system_time)
+}
+
+/// Indicators whose expiration fails to parse, as `(indicator id, error)`
+/// in indicator order. Their expirations are left out of `get_expiration`
+/// and `get_earliest_expiration`.
+pub fn invalid_expirations(record: &OTXRecord) -> Vec<(u128, DateParseError)> {
+    parsed_expirations(record).1
+}
+
+/// Expirations of the record's indicators, along with the id of each
+/// indicator whose expiration fails to parse and why.
+fn parsed_expirations(record: &OTXRecord) -> (Vec<std::time::SystemTime>, Vec<(u128, DateParseError)>) {
+    let mut expirations = vec![];
+    let mut failures = vec![];
+    for indicator in &record.indicators {
+        let Some(expiration) = indicator.expiration.as_deref() else {
+            continue;
+        };
+        match parse_iso8601(expiration) {
+            Ok(expiration) => expirations.push(expiration),
+            Err(e) => failures.push((indicator.id, e)),
+        }
+    }
+    (expirations, failures)
+}
+
+/// Whether an indicator is no longer live at `now`: flagged inactive, or past
+/// its expiration. An expiration that fails to parse doesn't expire it.
+pub fn is_expired(indicator: &OTXIndicator, now: std::time::SystemTime) -> bool {
+    indicator.is_active == 0
+        || indicator
+            .expiration
+            .as_deref()
+            .and_then(|expiration| parse_iso8601(expiration).ok())
+            .is_some_and(|expiration| expiration < now)
+}
+
+/// Counts the record's indicators as `(active, expired)` at `now`.
+pub fn count_indicators(record: &OTXRecord, now: std::time::SystemTime) -> (usize, usize) {
+    let expired = record.indicators.iter().filter(|indicator| is_expired(indicator, now)).count();
+    (record.indicators.len() - expired, expired)
+}
+
+/// Why an ISO 8601 date string could not be converted to a `SystemTime`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateParseError {
+    /// The string does not contain year, month, day, hour, minute and second.
+    TooFewFields,
+    /// The year is not a number.
+    InvalidYear,
+    /// The month is outside 1..=12.
+    InvalidMonth(u64),
+    /// The day does not exist in the given month and year.
+    InvalidDay(u64),
+    /// The hour is outside 0..=23.
+    InvalidHour(u64),
+    /// The minute is outside 0..=59.
+    InvalidMinute(u64),
+    /// The second is outside 0..=60 (60 allows for a leap second).
+    InvalidSecond(u64),
+    /// A field other than the year is not a number.
+    InvalidField(&'static str),
+    /// The UTC offset is not `Z`, `+HH`, `+HHMM` or `+HH:MM`.
+    InvalidOffset,
+    /// The date falls before the Unix epoch, after the year 9999 or cannot
+    /// be represented.
+    OutOfRange,
+}
+
+impl std::fmt::Display for DateParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateParseError::TooFewFields => write!(f, "Invalid date format"),
+            DateParseError::InvalidYear => write!(f, "Failed to parse year"),
+            DateParseError::InvalidMonth(month) => write!(f, "Invalid month: {}", month),
+            DateParseError::InvalidDay(day) => write!(f, "Invalid day: {}", day),
+            DateParseError::InvalidHour(hour) => write!(f, "Invalid hour: {}", hour),
+            DateParseError::InvalidMinute(minute) => write!(f, "Invalid minute: {}", minute),
+            DateParseError::InvalidSecond(second) => write!(f, "Invalid second: {}", second),
+            DateParseError::InvalidField(field) => write!(f, "Failed to parse {}", field),
+            DateParseError::InvalidOffset => write!(f, "Invalid UTC offset"),
+            DateParseError::OutOfRange => write!(f, "Date is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for DateParseError {}
+
+fn parse_iso8601(date_str: &str) -> std::result::Result<std::time::SystemTime, DateParseError> {
+    let (date_str, offset_seconds) = split_utc_offset(date_str)?;
+    let parts: Vec<&str> = date_str.split(['T', '-', ':', '.']).collect();
+    if parts.len() < 6 {
+        return Err(DateParseError::TooFewFields);
+    }
+
+    let field = |index: usize, name: &'static str| -> std::result::Result<u64, DateParseError> {
+        parts[index].parse().map_err(|_| DateParseError::InvalidField(name))
+    };
+    let year: u64 = parts[0].parse().map_err(|_| DateParseError::InvalidYear)?;
+    let month = field(1, "month")?;
+    let day = field(2, "day")?;
+    let hour = field(3, "hour")?;
+    let minute = field(4, "minute")?;
+    let second = field(5, "second")?;
+
+    if !(1970..=9999).contains(&year) {
+        return Err(DateParseError::OutOfRange);
+    }
+    if !(1..=12).contains(&month) {
+        return Err(DateParseError::InvalidMonth(month));
+    }
+    if !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(DateParseError::InvalidDay(day));
+    }
+    if hour > 23 {
+        return Err(DateParseError::InvalidHour(hour));
+    }
+    if minute > 59 {
+        return Err(DateParseError::InvalidMinute(minute));
+    }
+    if second > 60 {
+        return Err(DateParseError::InvalidSecond(second));
+    }
+
+    let days = days_before_year(year)
+        + days_before_month(year, month)
+        + (day - 1);
+
+    let local_seconds = (days * 86_400)
+        + (hour * 3_600) 
+        + (minute * 60) 
+        + second;
+    let utc_seconds = (local_seconds as i64)
+        .checked_sub(offset_seconds)
+        .filter(|seconds| *seconds >= 0)
+        .ok_or(DateParseError::OutOfRange)?;
+
+    let duration_since_epoch = std::time::Duration::new(utc_seconds as u64, 0);
+
+    Ok(std::time::SystemTime::UNIX_EPOCH + duration_since_epoch)
+}
+
+/// Strips a trailing `Z` or `+HH:MM`/`-HH:MM` offset from an ISO 8601 string,
+/// returning the remaining local date-time and the offset east of UTC in seconds.
+fn split_utc_offset(date_str: &str) -> std::result::Result<(&str, i64), DateParseError> {
+    if let Some(local) = date_str.strip_suffix(['Z', 'z']) {
+        return Ok((local, 0));
+    }
+
+    let time_start = match date_str.find('T') {
+        Some(index) => index,
+        None => return Ok((date_str, 0)),
+    };
+    let sign_index = match date_str[time_start..].rfind(['+', '-']) {
+        Some(index) => time_start + index,
+        None => return Ok((date_str, 0)),
+    };
+
+    let sign = if date_str[sign_index..].starts_with('-') { -1 } else { 1 };
+    let offset = date_str[sign_index + 1..].replace(':', "");
+    if (offset.len() != 2 && offset.len() != 4) || !offset.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(DateParseError::InvalidOffset);
+    }
+    let hours: i64 = offset[..2].parse().map_err(|_| DateParseError::InvalidOffset)?;
+    let minutes: i64 = if offset.len() == 4 {
+        offset[2..].parse().map_err(|_| DateParseError::InvalidOffset)?
+    } else {
+        0
+    };
+
+    Ok((&date_str[..sign_index], sign * (hours * 3_600 + minutes * 60)))
+}
+
+fn format_system_time(time: std::time::SystemTime) -> String {
+    let duration_since_epoch = time
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = duration_since_epoch.as_secs();
+
+    let mut day_of_year = seconds / 86_400;
+
+    let mut year = 1970;
+    while day_of_year >= days_in_year(year) {
+        day_of_year -= days_in_year(year);
+        year += 1;
+    }
+
+    let mut month = 1;
+    while month < 12 && day_of_year >= days_in_month(year, month) {
+        day_of_year -= days_in_month(year, month);
+        month += 1;
+    }
+    let day = day_of_year + 1;
+
+    let hour = (seconds % 86_400) / 3_600;
+    let minute = (seconds % 3_600) / 60;
+    let second = seconds % 60;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// `date` as RFC 3339 in UTC, such as "2024-01-01T12:00:00Z", or unchanged
+/// when it doesn't parse.
+pub fn normalize_timestamp(date: &str) -> String {
+    match parse_iso8601(date) {
+        Ok(time) => format!("{}Z", format_system_time(time)),
+        Err(_) => date.to_string(),
+    }
+}
+
+/// Whole hours from `modified` to `now`, zero for a date in the future and
+/// `None` for one that doesn't parse.
+pub fn age_hours(modified: &str, now: std::time::SystemTime) -> Option<u64> {
+    let modified = parse_iso8601(modified).ok()?;
+    Some(now.duration_since(modified).map(|age| age.as_secs() / 3_600).unwrap_or(0))
+}
+
+fn is_leap_year(year: u64) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_year(year: u64) -> u64 {
+    if is_leap_year(year) {
+        366
+    } else {
+        365
+    }
+}
+
+/// Days from 1970-01-01 to the start of `year`, which must be 1970 or later.
+fn days_before_year(year: u64) -> u64 {
+    let leap_years_through = |year: u64| year / 4 - year / 100 + year / 400;
+    365 * (year - 1970) + leap_years_through(year - 1) - leap_years_through(1969)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        _ => 28,
+    }
+}
+
+fn days_before_month(year: u64, month: u64) -> u64 {
+    (1..month).map(|m| days_in_month(year, m)).sum()
+}
+
+
+/// Keyword Matchers
+
+/// A classification rule: a literal keyword, or a regular expression for
+/// identifiers no fixed keyword can capture. Both match lowercased text and
+/// only as whole words.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Rule {
+    Keyword(&'static str),
+    Pattern(&'static str),
+}
+
+impl Rule {
+    /// The keyword or regex source, as reported in evidence.
+    pub fn source(&self) -> &'static str {
+        match self {
+            Rule::Keyword(source) | Rule::Pattern(source) => source,
+        }
+    }
+}
+
+/// Single-pass matcher over one rule table. Every keyword is compiled into
+/// one Aho-Corasick automaton, so a record's text is scanned once no matter
+/// how many keywords the table holds. Regex rules are compiled once alongside
+/// it and run separately. Both ignore ASCII case, so text needs no lowercased
+/// copy before matching; other scripts still rely on `normalize`.
+pub struct KeywordMatcher<T> {
+    automaton: AhoCorasick,
+    keywords: Vec<&'static str>,
+    labels: Vec<T>,
+    weights: Vec<f32>,
+    patterns: Vec<(Regex, KeywordHit<T>)>,
+    tags: HashMap<&'static str, (T, f32)>,
+}
+
+/// One whole-word rule hit and the label and weight its table gives it. For a
+/// regex rule `keyword` is the regex source.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KeywordHit<T> {
+    pub keyword: &'static str,
+    pub label: T,
+    pub weight: f32,
+    pub pattern: bool,
+}
+
+impl<T: Copy> KeywordMatcher<T> {
+    /// Builds a matcher where every keyword carries full weight.
+    pub fn new(keywords: HashMap<&'static str, T>) -> Self {
+        Self::weighted(keywords.into_iter().map(|(keyword, label)| (keyword, (label, STRONG))).collect())
+    }
+
+    pub fn weighted(keywords: HashMap<&'static str, (T, f32)>) -> Self {
+        Self::from_rules(keywords.into_iter().map(|(keyword, entry)| (Rule::Keyword(keyword), entry)).collect())
+    }
+
+    pub fn from_rules(rules: Vec<(Rule, (T, f32))>) -> Self {
+        let mut keywords: Vec<&'static str> = vec![];
+        let mut labels: Vec<T> = vec![];
+        let mut weights: Vec<f32> = vec![];
+        let mut patterns: Vec<(Regex, KeywordHit<T>)> = vec![];
+        let mut tags: HashMap<&'static str, (T, f32)> = HashMap::new();
+        for (rule, (label, weight)) in rules {
+            match rule {
+                Rule::Keyword(keyword) => {
+                    tags.insert(keyword, (label, weight));
+                    keywords.push(keyword);
+                    labels.push(label);
+                    weights.push(weight);
+                }
+                Rule::Pattern(source) => {
+                    let regex = RegexBuilder::new(source)
+                        .case_insensitive(true)
+                        .build()
+                        .expect("regex rules should compile");
+                    patterns.push((regex, KeywordHit { keyword: source, label, weight, pattern: true }));
+                }
+            }
+        }
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&keywords)
+            .expect("keyword tables should build an automaton");
+        KeywordMatcher { automaton, keywords, labels, weights, patterns, tags }
+    }
+
+    /// Adds tag-only aliases, such as malware family names, that classify a
+    /// record when a tag equals them but are not searched for in free text.
+    pub fn with_tag_aliases(mut self, aliases: HashMap<&'static str, T>) -> Self {
+        self.tags.extend(aliases.into_iter().map(|(alias, label)| (alias, (label, STRONG))));
+        self
+    }
+
+    /// The label and weight of the keyword or tag alias that `tag` equals,
+    /// ignoring ASCII case. `tag` must already be folded.
+    pub fn tag_label(&self, tag: &str) -> Option<(T, f32)> {
+        self.tags.get(tag.to_ascii_lowercase().as_str()).copied()
+    }
+
+    /// Returns every whole-word rule hit in `text`, in the order the hits end
+    /// in the text. Negated hits are dropped, overlapping keyword hits are
+    /// resolved by `most_specific_hits`, and regex matches made only of hex
+    /// digits are file hashes, not identifiers, and are skipped.
+    pub fn hits(&self, text: &str) -> Vec<KeywordHit<T>> {
+        let keyword_hits = self.automaton
+            .find_overlapping_iter(text)
+            .filter(|m| is_whole_word(text, m.start(), m.end()) && !is_negated(text, m.start()))
+            .map(|m| {
+                let index = m.pattern().as_usize();
+                let hit = KeywordHit {
+                    keyword: self.keywords[index],
+                    label: self.labels[index],
+                    weight: self.weights[index],
+                    pattern: false,
+                };
+                (m.start(), m.end(), hit)
+            })
+            .collect();
+        let keyword_hits = most_specific_hits(keyword_hits).into_iter().map(|(_, end, hit)| (end, hit));
+        let pattern_hits = self.patterns.iter().flat_map(|(regex, hit)| {
+            regex
+                .find_iter(text)
+                .filter(|m| is_whole_word(text, m.start(), m.end()) && !is_negated(text, m.start()))
+                .filter(|m| !m.as_str().chars().all(|c| c.is_ascii_hexdigit()))
+                .map(|m| (m.end(), *hit))
+        });
+        let mut hits: Vec<(usize, KeywordHit<T>)> = keyword_hits.chain(pattern_hits).collect();
+        hits.sort_by_key(|(end, _)| *end);
+        hits.into_iter().map(|(_, hit)| hit).collect()
+    }
+
+    /// Returns the label of every whole-word keyword hit in `text`, in the
+    /// order the hits end in the text. Labels repeat once per hit.
+    pub fn find_all(&self, text: &str) -> Vec<T> {
+        self.hits(text).into_iter().map(|hit| hit.label).collect()
+    }
+}
+
+impl<T: Copy + PartialEq> KeywordMatcher<T> {
+    /// Returns each distinct label found across `texts`, in order of first hit.
+    pub fn find(&self, texts: &[&str]) -> Vec<T> {
+        let mut found: Vec<T> = vec![];
+        for label in texts.iter().flat_map(|text| self.find_all(text)) {
+            if !found.contains(&label) {
+                found.push(label);
+            }
+        }
+        found
+    }
+}
+
+/// Keeps one hit per stretch of text. A hit inside a longer one is dropped,
+/// so "remote access trojan" doesn't also count as "trojan". Of hits that
+/// only partly overlap, as "sql injection" and "injection attack" do in "sql
+/// injection attack", the heavier one is kept, then the longer, then the
+/// earlier.
+fn most_specific_hits<T: Copy>(hits: Vec<(usize, usize, KeywordHit<T>)>) -> Vec<(usize, usize, KeywordHit<T>)> {
+    let mut outermost: Vec<(usize, usize, KeywordHit<T>)> = hits
+        .iter()
+        .filter(|(start, end, _)| {
+            !hits.iter().any(|(other_start, other_end, _)| {
+                other_start <= start && end <= other_end && other_end - other_start > end - start
+            })
+        })
+        .copied()
+        .collect();
+    outermost.sort_by(|(a_start, a_end, a), (b_start, b_end, b)| {
+        b.weight
+            .total_cmp(&a.weight)
+            .then((b_end - b_start).cmp(&(a_end - a_start)))
+            .then(a_start.cmp(b_start))
+    });
+    let mut kept: Vec<(usize, usize, KeywordHit<T>)> = vec![];
+    for (start, end, hit) in outermost {
+        if kept.iter().all(|(kept_start, kept_end, _)| end <= *kept_start || *kept_end <= start) {
+            kept.push((start, end, hit));
+        }
+    }
+    kept
+}
+
+/// Whether `haystack[start..end]` is not part of a larger word, so "dos" does
+/// not fire on "windows" while "sql injection" still matches as a phrase.
+fn is_whole_word(haystack: &str, start: usize, end: usize) -> bool {
+    let before = haystack[..start].chars().next_back();
+    let after = haystack[end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+/// Whether `c` continues a word. Scripts written without spaces between words
+/// (Chinese, Japanese, Korean) never do, so "使用phishing工具" still matches.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() && !matches!(c,
+        '\u{3040}'..='\u{30FF}'     // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}'   // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}'   // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{2FA1F}' // CJK Extensions B onwards
+    )
+}
+
+/// Whether `keyword` occurs in `haystack` delimited by non-alphanumeric
+/// boundaries. Multi-word keywords such as "denial of service" match as a
+/// phrase. The matchers apply the same rule to every hit.
+pub fn contains_word(haystack: &str, keyword: &str) -> bool {
+    haystack
+        .match_indices(keyword)
+        .any(|(start, m)| is_whole_word(haystack, start, start + m.len()))
+}
+
+/// Combines a keyword table with its regex rules into one rule table.
+fn rules<T: Copy>(keywords: &HashMap<&'static str, (T, f32)>, patterns: Vec<(Rule, (T, f32))>) -> Vec<(Rule, (T, f32))> {
+    keywords.iter().map(|(keyword, entry)| (Rule::Keyword(keyword), *entry)).chain(patterns).collect()
+}
+
+/// How many words before a hit are searched for a negation cue. Kept short so
+/// a cue negates only the phrase it is attached to: in "not ransomware but a
+/// wiper" the wiper still counts.
+const NEGATION_WINDOW: usize = 2;
+
+/// Words that negate a hit following them, as in "no ransomware component"
+/// or "unrelated ddos activity".
+const NEGATION_CUES: &[&str] = &["no", "not", "non", "nor", "never", "neither", "without", "unrelated"];
+
+/// Whether a hit starting at `start` is negated by a cue among the preceding
+/// `NEGATION_WINDOW` words of the same sentence. Contractions such as "isn't"
+/// count as cues.
+fn is_negated(haystack: &str, start: usize) -> bool {
+    let sentence = haystack[..start].rsplit(['.', ';', '!', '?']).next().unwrap_or_default();
+    sentence
+        .split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '\u{2019}')
+        .filter(|word| !word.is_empty())
+        .rev()
+        .take(NEGATION_WINDOW)
+        .map(|word| word.to_ascii_lowercase())
+        .any(|word| NEGATION_CUES.contains(&word.as_str()) || word.ends_with("n't") || word.ends_with("n\u{2019}t"))
+}
+
+pub fn attack_type_matcher() -> &'static KeywordMatcher<AttackType> {
+    &keyword_matchers().attack_types
+}
+
+pub fn attack_vector_matcher() -> &'static KeywordMatcher<AttackVector> {
+    &keyword_matchers().attack_vectors
+}
+
+pub fn urgency_matcher() -> &'static KeywordMatcher<Urgency> {
+    &keyword_matchers().urgency
+}
+
+pub fn target_matcher() -> &'static KeywordMatcher<Target> {
+    &keyword_matchers().targets
+}
+
+pub fn sector_matcher() -> &'static KeywordMatcher<Sector> {
+    static MATCHER: OnceLock<KeywordMatcher<Sector>> = OnceLock::new();
+    MATCHER.get_or_init(|| KeywordMatcher::new(sector_keywords().clone()))
+}
+
+pub fn os_target_matcher() -> &'static KeywordMatcher<OsTarget> {
+    static MATCHER: OnceLock<KeywordMatcher<OsTarget>> = OnceLock::new();
+    MATCHER.get_or_init(|| KeywordMatcher::new(os_target_keywords().clone()))
+}
+
+
+/// Keyword Configuration
+
+/// One keyword of a `KeywordConfig` table. The weight defaults to `STRONG`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeywordEntry<T> {
+    pub keyword: String,
+    pub label: T,
+    #[serde(default = "strong_weight")]
+    pub weight: f32,
+}
+
+fn strong_weight() -> f32 {
+    STRONG
+}
+
+/// The keyword tables behind the attack type, vector, target and urgency
+/// classifiers, so they can be tuned without rebuilding the module. The
+/// default holds the built-in tables. Regex rules and tag aliases are not
+/// configurable and apply whichever tables are in use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeywordConfig {
+    pub attack_types: Vec<KeywordEntry<AttackType>>,
+    pub attack_vectors: Vec<KeywordEntry<AttackVector>>,
+    pub targets: Vec<KeywordEntry<Target>>,
+    pub urgency: Vec<KeywordEntry<Urgency>>,
+}
+
+impl Default for KeywordConfig {
+    fn default() -> Self {
+        let urgency: HashMap<_, _> = urgency_keywords().iter().map(|(keyword, label)| (*keyword, (*label, STRONG))).collect();
+        KeywordConfig {
+            attack_types: keyword_entries(attack_type_keywords()),
+            attack_vectors: keyword_entries(attack_vector_keywords()),
+            targets: keyword_entries(target_keywords()),
+            urgency: keyword_entries(&urgency),
+        }
+    }
+}
+
+impl KeywordConfig {
+    /// Parses and validates tables written as JSON. Labels use the names
+    /// records are serialized with, and an unknown name is an error.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let config: KeywordConfig =
+            serde_json::from_str(json).map_err(|e| eyre::eyre!("invalid keyword config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Rejects keywords that are empty once normalized, weights that are not
+    /// positive, and keywords listed twice in one table.
+    pub fn validate(&self) -> Result<()> {
+        validate_keyword_table("attack_types", &self.attack_types)?;
+        validate_keyword_table("attack_vectors", &self.attack_vectors)?;
+        validate_keyword_table("targets", &self.targets)?;
+        validate_keyword_table("urgency", &self.urgency)
+    }
+
+    /// Builds the classifier matchers for these tables, with the
+    /// `extra_attack_keywords` init param merged into the attack types.
+    /// Keywords are normalized and live as long as the module, like the
+    /// built-in ones.
+    pub fn matchers(&self) -> KeywordMatchers {
+        self.matchers_with_extras(&config().extra_attack_keywords)
+    }
+
+    fn matchers_with_extras(&self, extra_attack_keywords: &HashMap<&'static str, AttackType>) -> KeywordMatchers {
+        KeywordMatchers::from_tables(
+            &with_extra_keywords(leaked_keyword_table(&self.attack_types), extra_attack_keywords),
+            &leaked_keyword_table(&self.attack_vectors),
+            &leaked_keyword_table(&self.targets),
+            &leaked_keyword_table(&self.urgency),
+        )
+    }
+}
+
+/// The built-in table as config entries, sorted by keyword.
+fn keyword_entries<T: Copy>(table: &HashMap<&'static str, (T, f32)>) -> Vec<KeywordEntry<T>> {
+    let mut entries: Vec<KeywordEntry<T>> = table
+        .iter()
+        .map(|(keyword, (label, weight))| KeywordEntry { keyword: keyword.to_string(), label: *label, weight: *weight })
+        .collect();
+    entries.sort_by(|a, b| a.keyword.cmp(&b.keyword));
+    entries
+}
+
+fn validate_keyword_table<T>(table: &str, entries: &[KeywordEntry<T>]) -> Result<()> {
+    let mut seen: HashSet<String> = HashSet::new();
+    for entry in entries {
+        let keyword = normalize(&entry.keyword);
+        if keyword.is_empty() {
+            return Err(eyre::eyre!("{} has an empty keyword: {:?}", table, entry.keyword));
+        }
+        if !(entry.weight.is_finite() && entry.weight > 0.0) {
+            return Err(eyre::eyre!("{} keyword {:?} must have a positive weight, got {}", table, entry.keyword, entry.weight));
+        }
+        if !seen.insert(keyword) {
+            return Err(eyre::eyre!("{} lists keyword {:?} more than once", table, entry.keyword));
+        }
+    }
+    Ok(())
+}
+
+fn leaked_keyword_table<T: Copy>(entries: &[KeywordEntry<T>]) -> HashMap<&'static str, (T, f32)> {
+    entries
+        .iter()
+        .map(|entry| {
+            let keyword: &'static str = Box::leak(normalize(&entry.keyword).into_boxed_str());
+            (keyword, (entry.label, entry.weight))
+        })
+        .collect()
+}
+
+/// The matchers the attack type, vector, target and urgency classifiers use.
+pub struct KeywordMatchers {
+    pub attack_types: KeywordMatcher<AttackType>,
+    pub attack_vectors: KeywordMatcher<AttackVector>,
+    pub targets: KeywordMatcher<Target>,
+    pub urgency: KeywordMatcher<Urgency>,
+}
+
+impl KeywordMatchers {
+    fn from_tables(
+        attack_types: &HashMap<&'static str, (AttackType, f32)>,
+        attack_vectors: &HashMap<&'static str, (AttackVector, f32)>,
+        targets: &HashMap<&'static str, (Target, f32)>,
+        urgency: &HashMap<&'static str, (Urgency, f32)>,
+    ) -> Self {
+        KeywordMatchers {
+            attack_types: KeywordMatcher::from_rules(rules(attack_types, attack_type_patterns()))
+                .with_tag_aliases(malware_family_map()),
+            attack_vectors: KeywordMatcher::from_rules(rules(attack_vectors, attack_vector_patterns())),
+            targets: KeywordMatcher::from_rules(rules(targets, target_patterns()))
+                .with_tag_aliases(target_tag_aliases()),
+            urgency: KeywordMatcher::from_rules(rules(urgency, urgency_patterns())),
+        }
+    }
+
+    /// The built-in tables, with `extra_attack_keywords` merged into the
+    /// attack types.
+    fn built_in(extra_attack_keywords: &HashMap<&'static str, AttackType>) -> Self {
+        let urgency: HashMap<_, _> = urgency_keywords().iter().map(|(keyword, label)| (*keyword, (*label, STRONG))).collect();
+        Self::from_tables(
+            &with_extra_keywords(attack_type_keywords().clone(), extra_attack_keywords),
+            attack_vector_keywords(),
+            target_keywords(),
+            &urgency,
+        )
+    }
+}
+
+static KEYWORD_MATCHERS: OnceLock<KeywordMatchers> = OnceLock::new();
+
+/// Makes the classifiers use `config` in place of the built-in tables, still
+/// adding `extra_attack_keywords`. Must be called after init and before the
+/// first record is classified; the tables cannot change once in use.
+pub fn use_keyword_config(config: &KeywordConfig) -> Result<()> {
+    config.validate()?;
+    KEYWORD_MATCHERS
+        .set(config.matchers())
+        .map_err(|_| eyre::eyre!("keyword tables are already in use"))
+}
+
+/// The classifier matchers. Before init they are built without
+/// `extra_attack_keywords` and not kept, so classifying early doesn't leave
+/// the init params out of the tables for good.
+fn keyword_matchers() -> &'static KeywordMatchers {
+    static BEFORE_INIT: OnceLock<KeywordMatchers> = OnceLock::new();
+    match CONFIG.get() {
+        Some(config) => KEYWORD_MATCHERS.get_or_init(|| KeywordMatchers::built_in(&config.extra_attack_keywords)),
+        None => BEFORE_INIT.get_or_init(|| KeywordMatchers::built_in(&HashMap::new())),
+    }
+}
+
+
+/// Hashmap functions for synonymous cases
+
+// Keywords are written in normalized form: lowercase, with words separated by
+// single spaces and no hyphens or underscores.
+// Each table is built on first use and shared for the life of the module.
+
+/// Weight of a specific keyword that classifies a record on its own.
+pub const STRONG: f32 = 1.0;
+/// Weight of a generic keyword that needs corroboration from another keyword.
+pub const WEAK: f32 = 0.4;
+
+pub fn attack_type_keywords() -> &'static HashMap<&'static str, (AttackType, f32)> {
+    static KEYWORDS: OnceLock<HashMap<&'static str, (AttackType, f32)>> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        let mut m = HashMap::new();
+
+        m.insert("ransom", (AttackType::Ransomware, STRONG));
+        m.insert("ransomware", (AttackType::Ransomware, STRONG));
+        m.insert("locker", (AttackType::Ransomware, WEAK));
+        m.insert("cryptolocker", (AttackType::Ransomware, STRONG));
+        m.insert("encryptor", (AttackType::Ransomware, STRONG));
+        m.insert("crypto malware", (AttackType::Ransomware, STRONG));
+
+        m.insert("malware", (AttackType::Malware, STRONG));
+        m.insert("virus", (AttackType::Malware, STRONG));
+        m.insert("worm", (AttackType::Malware, STRONG));
+        m.insert("adware", (AttackType::Malware, STRONG));
+        m.insert("rootkit", (AttackType::Malware, STRONG));
+        m.insert("keylogger", (AttackType::Malware, STRONG));
+
+        m.insert("ddos", (AttackType::Ddos, STRONG));
+        m.insert("dos", (AttackType::Ddos, STRONG));
+        m.insert("denial of service", (AttackType::Ddos, STRONG));
+        m.insert("distributed denial of service", (AttackType::Ddos, STRONG));
+        m.insert("flood attack", (AttackType::Ddos, STRONG));
+        m.insert("syn flood", (AttackType::Ddos, STRONG));
+        m.insert("amplification attack", (AttackType::Ddos, STRONG));
+
+        m.insert("botnet", (AttackType::Botnet, STRONG));
+        m.insert("bot network", (AttackType::Botnet, STRONG));
+        m.insert("zombie network", (AttackType::Botnet, STRONG));
+        m.insert("c&c", (AttackType::Botnet, STRONG));
+        m.insert("command and control", (AttackType::Botnet, STRONG));
+
+        m.insert("phish", (AttackType::Phishing, STRONG));
+        m.insert("phishing", (AttackType::Phishing, STRONG));
+        m.insert("spearphish", (AttackType::Phishing, STRONG));
+        m.insert("spearphishing", (AttackType::Phishing, STRONG));
+        m.insert("spear phishing", (AttackType::Phishing, STRONG));
+        m.insert("whaling", (AttackType::Phishing, STRONG));
+        m.insert("credential harvesting", (AttackType::Phishing, STRONG));
+        m.insert("email scam", (AttackType::Phishing, STRONG));
+        m.insert("smishing", (AttackType::Phishing, STRONG));
+        m.insert("sms phishing", (AttackType::Phishing, STRONG));
+        m.insert("vishing", (AttackType::Phishing, STRONG));
+
+        m.insert("bec", (AttackType::BusinessEmailCompromise, STRONG));
+        m.insert("business email compromise", (AttackType::BusinessEmailCompromise, STRONG));
+        m.insert("vendor email compromise", (AttackType::BusinessEmailCompromise, STRONG));
+        m.insert("ceo fraud", (AttackType::BusinessEmailCompromise, STRONG));
+        m.insert("invoice fraud", (AttackType::BusinessEmailCompromise, STRONG));
+        m.insert("wire fraud", (AttackType::BusinessEmailCompromise, STRONG));
+        m.insert("wire transfer fraud", (AttackType::BusinessEmailCompromise, STRONG));
+        m.insert("payroll diversion", (AttackType::BusinessEmailCompromise, STRONG));
+
+        m.insert("trojan", (AttackType::Trojan, STRONG));
+        m.insert("trojan horse", (AttackType::Trojan, STRONG));
+        m.insert("dropper", (AttackType::Trojan, STRONG));
+        m.insert("backdoor", (AttackType::Trojan, STRONG));
+
+        m.insert("rat", (AttackType::RemoteAccessTrojan, STRONG));
+        m.insert("remote access trojan", (AttackType::RemoteAccessTrojan, STRONG));
+        m.insert("remote administration tool", (AttackType::RemoteAccessTrojan, WEAK));
+        m.insert("njrat", (AttackType::RemoteAccessTrojan, STRONG));
+        m.insert("asyncrat", (AttackType::RemoteAccessTrojan, STRONG));
+        m.insert("remcos", (AttackType::RemoteAccessTrojan, STRONG));
+        m.insert("quasar rat", (AttackType::RemoteAccessTrojan, STRONG));
+        m.insert("darkcomet", (AttackType::RemoteAccessTrojan, STRONG));
+
+        m.insert("stealer", (AttackType::InfoStealer, STRONG));
+        m.insert("infostealer", (AttackType::InfoStealer, STRONG));
+        m.insert("info stealer", (AttackType::InfoStealer, STRONG));
+        m.insert("information stealer", (AttackType::InfoStealer, STRONG));
+        m.insert("credential stealer", (AttackType::InfoStealer, STRONG));
+        m.insert("password stealer", (AttackType::InfoStealer, STRONG));
+        m.insert("redline", (AttackType::InfoStealer, STRONG));
+        m.insert("lumma", (AttackType::InfoStealer, STRONG));
+
+        m.insert("spyware", (AttackType::Spyware, STRONG));
+        m.insert("snoopware", (AttackType::Spyware, STRONG));
+        m.insert("tracking software", (AttackType::Spyware, WEAK));
+        m.insert("monitoring tool", (AttackType::Spyware, WEAK));
+
+        m.insert("brute force", (AttackType::BruteForce, STRONG));
+        m.insert("bruteforce", (AttackType::BruteForce, STRONG));
+        m.insert("credential stuffing", (AttackType::BruteForce, STRONG));
+        m.insert("password cracking", (AttackType::BruteForce, STRONG));
+        m.insert("dictionary attack", (AttackType::BruteForce, STRONG));
+
+        m.insert("sql injection", (AttackType::SQLInjection, STRONG));
+        m.insert("sqli", (AttackType::SQLInjection, STRONG));
+        m.insert("injection attack", (AttackType::SQLInjection, WEAK));
+
+        m.insert("cryptojacking", (AttackType::Cryptojacking, STRONG));
+        m.insert("cryptojacker", (AttackType::Cryptojacking, STRONG));
+        m.insert("cryptomining", (AttackType::Cryptojacking, STRONG));
+        m.insert("crypto mining", (AttackType::Cryptojacking, STRONG));
+        m.insert("cryptominer", (AttackType::Cryptojacking, STRONG));
+        m.insert("coinminer", (AttackType::Cryptojacking, STRONG));
+        m.insert("coin miner", (AttackType::Cryptojacking, STRONG));
+        m.insert("xmrig", (AttackType::Cryptojacking, STRONG));
+        m.insert("monero miner", (AttackType::Cryptojacking, STRONG));
+        m.insert("monero mining", (AttackType::Cryptojacking, STRONG));
+        m.insert("coinhive", (AttackType::Cryptojacking, STRONG));
+
+        m.insert("wiper", (AttackType::Wiper, STRONG));
+        m.insert("wiper malware", (AttackType::Wiper, STRONG));
+        m.insert("data wiper", (AttackType::Wiper, STRONG));
+        m.insert("disk wiper", (AttackType::Wiper, STRONG));
+        m.insert("disk wiping", (AttackType::Wiper, STRONG));
+        m.insert("disk wipe", (AttackType::Wiper, STRONG));
+        m.insert("shamoon", (AttackType::Wiper, STRONG));
+        m.insert("killdisk", (AttackType::Wiper, STRONG));
+        m.insert("megadeleter", (AttackType::Wiper, STRONG));
+        m.insert("data destruction", (AttackType::Wiper, STRONG));
+        m.insert("destructive malware", (AttackType::Wiper, STRONG));
+        m.insert("mbr overwrite", (AttackType::Wiper, STRONG));
+
+        m.insert("apt", (AttackType::APT, STRONG));
+        m.insert("advanced persistent threat", (AttackType::APT, STRONG));
+        m.insert("nation state", (AttackType::APT, STRONG));
+        m.insert("state sponsored", (AttackType::APT, STRONG));
+
+        m.insert("zero day", (AttackType::ZeroDay, STRONG));
+        m.insert("0day", (AttackType::ZeroDay, STRONG));
+        m.insert("exploit kit", (AttackType::Exploit, STRONG));
+        m.insert("exploit chain", (AttackType::Exploit, STRONG));
+        m.insert("mass exploitation", (AttackType::Exploit, STRONG));
+        m.insert("rce", (AttackType::Exploit, STRONG));
+        m.insert("remote code execution", (AttackType::Exploit, STRONG));
+        m.insert("exploit", (AttackType::Exploit, WEAK));
+        m.insert("exploitation", (AttackType::Exploit, WEAK));
+        m.insert("exploited", (AttackType::Exploit, WEAK));
+        m.insert("proof of concept", (AttackType::Exploit, WEAK));
+        m.insert("poc", (AttackType::Exploit, WEAK));
+        m.insert("magecart", (AttackType::WebSkimming, STRONG));
+        m.insert("skimmer", (AttackType::WebSkimming, STRONG));
+        m.insert("web skimmer", (AttackType::WebSkimming, STRONG));
+        m.insert("card skimming", (AttackType::WebSkimming, STRONG));
+        m.insert("formjacking", (AttackType::WebSkimming, STRONG));
+        m.insert("e skimming", (AttackType::WebSkimming, STRONG));
+
+        m.insert("defacement", (AttackType::Defacement, STRONG));
+        m.insert("defaced", (AttackType::Defacement, STRONG));
+        m.insert("website defacement", (AttackType::Defacement, STRONG));
+        m.insert("hacktivist defacement", (AttackType::Defacement, STRONG));
+
+        m.insert("dns hijack", (AttackType::DnsHijacking, STRONG));
+        m.insert("dns hijacking", (AttackType::DnsHijacking, STRONG));
+        m.insert("dns hijacked", (AttackType::DnsHijacking, STRONG));
+        m.insert("nameserver compromise", (AttackType::DnsHijacking, STRONG));
+        m.insert("nameserver hijacking", (AttackType::DnsHijacking, STRONG));
+
+        m.insert("typosquat", (AttackType::Typosquatting, STRONG));
+        m.insert("typosquatting", (AttackType::Typosquatting, STRONG));
+        m.insert("typosquatted", (AttackType::Typosquatting, STRONG));
+        m.insert("lookalike domain", (AttackType::Typosquatting, STRONG));
+        m.insert("lookalike domains", (AttackType::Typosquatting, STRONG));
+        m.insert("homoglyph domain", (AttackType::Typosquatting, STRONG));
+        m.insert("homoglyph domains", (AttackType::Typosquatting, STRONG));
+
+        m.insert("watering hole", (AttackType::WateringHole, STRONG));
+        m.insert("strategic web compromise", (AttackType::WateringHole, STRONG));
+
+        m.insert("reconnaissance", (AttackType::Reconnaissance, STRONG));
+        m.insert("port scan", (AttackType::Reconnaissance, STRONG));
+        m.insert("port scans", (AttackType::Reconnaissance, STRONG));
+        m.insert("port scanning", (AttackType::Reconnaissance, STRONG));
+        m.insert("nmap", (AttackType::Reconnaissance, STRONG));
+        m.insert("masscan", (AttackType::Reconnaissance, STRONG));
+        m.insert("scanning", (AttackType::Reconnaissance, WEAK));
+        m.insert("probing", (AttackType::Reconnaissance, WEAK));
+        m.insert("enumeration", (AttackType::Reconnaissance, WEAK));
+
+        m.insert("database injection", (AttackType::SQLInjection, STRONG));
+        m.insert("blind sql", (AttackType::SQLInjection, STRONG));
+        m.insert("error based injection", (AttackType::SQLInjection, STRONG));
+        m.insert("union based injection", (AttackType::SQLInjection, STRONG));
+
+        // Spanish, Portuguese, French, German and Russian terms for the main
+        // categories, with accent-free spellings where writers often drop them.
+        m.insert("secuestro de datos", (AttackType::Ransomware, STRONG));
+        m.insert("sequestro de dados", (AttackType::Ransomware, STRONG));
+        m.insert("rançongiciel", (AttackType::Ransomware, STRONG));
+        m.insert("rancongiciel", (AttackType::Ransomware, STRONG));
+        m.insert("logiciel de rançon", (AttackType::Ransomware, STRONG));
+        m.insert("erpressungstrojaner", (AttackType::Ransomware, STRONG));
+        m.insert("erpressersoftware", (AttackType::Ransomware, STRONG));
+        m.insert("вымогатель", (AttackType::Ransomware, STRONG));
+        m.insert("программа вымогатель", (AttackType::Ransomware, STRONG));
+        m.insert("шифровальщик", (AttackType::Ransomware, STRONG));
+
+        m.insert("suplantación de identidad", (AttackType::Phishing, STRONG));
+        m.insert("suplantacion de identidad", (AttackType::Phishing, STRONG));
+        m.insert("hameçonnage", (AttackType::Phishing, STRONG));
+        m.insert("hameconnage", (AttackType::Phishing, STRONG));
+        m.insert("фишинг", (AttackType::Phishing, STRONG));
+        m.insert("фишинга", (AttackType::Phishing, STRONG));
+        m.insert("фишинговая", (AttackType::Phishing, STRONG));
+        m.insert("фишинговые", (AttackType::Phishing, STRONG));
+
+        m.insert("red de bots", (AttackType::Botnet, STRONG));
+        m.insert("rede de bots", (AttackType::Botnet, STRONG));
+        m.insert("réseau de zombies", (AttackType::Botnet, STRONG));
+        m.insert("reseau de zombies", (AttackType::Botnet, STRONG));
+        m.insert("botnetz", (AttackType::Botnet, STRONG));
+        m.insert("ботнет", (AttackType::Botnet, STRONG));
+        m.insert("ботнета", (AttackType::Botnet, STRONG));
+
+        m.insert("denegación de servicio", (AttackType::Ddos, STRONG));
+        m.insert("denegacion de servicio", (AttackType::Ddos, STRONG));
+        m.insert("negação de serviço", (AttackType::Ddos, STRONG));
+        m.insert("negacao de servico", (AttackType::Ddos, STRONG));
+        m.insert("déni de service", (AttackType::Ddos, STRONG));
+        m.insert("deni de service", (AttackType::Ddos, STRONG));
+        m.insert("überlastungsangriff", (AttackType::Ddos, STRONG));
+        m.insert("отказ в обслуживании", (AttackType::Ddos, STRONG));
+        m.insert("ддос", (AttackType::Ddos, STRONG));
+
+        m.insert("troyano", (AttackType::Trojan, STRONG));
+        m.insert("cavalo de troia", (AttackType::Trojan, STRONG));
+        m.insert("cheval de troie", (AttackType::Trojan, STRONG));
+        m.insert("trojaner", (AttackType::Trojan, STRONG));
+        m.insert("троян", (AttackType::Trojan, STRONG));
+        m.insert("троянец", (AttackType::Trojan, STRONG));
+        m.insert("троянская программа", (AttackType::Trojan, STRONG));
+
+        m
+    })
+}
+
+/// Adds user-supplied keywords to a table at full weight, replacing any
+/// built-in entry for the same keyword.
+fn with_extra_keywords<T: Copy>(
+    mut keywords: HashMap<&'static str, (T, f32)>,
+    extra: &HashMap<&'static str, T>,
+) -> HashMap<&'static str, (T, f32)> {
+    keywords.extend(extra.iter().map(|(keyword, label)| (*keyword, (*label, STRONG))));
+    keywords
+}
+
+pub fn attack_vector_keywords() -> &'static HashMap<&'static str, (AttackVector, f32)> {
+    static KEYWORDS: OnceLock<HashMap<&'static str, (AttackVector, f32)>> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        let mut m = HashMap::new();
+
+        m.insert("email", (AttackVector::Email, STRONG));
+        m.insert("phishing", (AttackVector::Email, STRONG));
+        m.insert("spearphish", (AttackVector::Email, STRONG));
+        m.insert("spearphishing", (AttackVector::Email, STRONG));
+        m.insert("spoofing", (AttackVector::Email, WEAK));
+        m.insert("bec", (AttackVector::Email, STRONG));
+
+        m.insert("web", (AttackVector::WebApplication, WEAK));
+        m.insert("xss", (AttackVector::WebApplication, STRONG));
+        m.insert("cross site scripting", (AttackVector::WebApplication, STRONG));
+        m.insert("sql injection", (AttackVector::WebApplication, STRONG));
+        m.insert("sqli", (AttackVector::WebApplication, STRONG));
+        m.insert("csrf", (AttackVector::WebApplication, STRONG));
+        m.insert("directory traversal", (AttackVector::WebApplication, STRONG));
+        m.insert("magecart", (AttackVector::WebApplication, STRONG));
+        m.insert("skimmer", (AttackVector::WebApplication, STRONG));
+        m.insert("web skimmer", (AttackVector::WebApplication, STRONG));
+        m.insert("card skimming", (AttackVector::WebApplication, STRONG));
+        m.insert("formjacking", (AttackVector::WebApplication, STRONG));
+        m.insert("e skimming", (AttackVector::WebApplication, STRONG));
+        m.insert("website", (AttackVector::WebApplication, WEAK));
+        m.insert("website defacement", (AttackVector::WebApplication, STRONG));
+        m.insert("defaced", (AttackVector::WebApplication, STRONG));
+
+        m.insert("network", (AttackVector::Network, WEAK));
+        m.insert("ddos", (AttackVector::Network, STRONG));
+        m.insert("denial of service", (AttackVector::Network, STRONG));
+        m.insert("port scan", (AttackVector::Network, STRONG));
+        m.insert("port scans", (AttackVector::Network, STRONG));
+        m.insert("port scanning", (AttackVector::Network, STRONG));
+        m.insert("mitm", (AttackVector::Network, STRONG));
+        m.insert("man in the middle", (AttackVector::Network, STRONG));
+
+        m.insert("cloud", (AttackVector::CloudService, WEAK));
+        m.insert("aws", (AttackVector::CloudService, STRONG));
+        m.insert("gcp", (AttackVector::CloudService, STRONG));
+        m.insert("azure", (AttackVector::CloudService, STRONG));
+        m.insert("bucket", (AttackVector::CloudService, WEAK));
+        m.insert("s3", (AttackVector::CloudService, STRONG));
+        m.insert("misconfig", (AttackVector::CloudService, WEAK));
+        m.insert("storage exposure", (AttackVector::CloudService, STRONG));
+
+        m.insert("supply chain", (AttackVector::SupplyChain, STRONG));
+        m.insert("dependency confusion", (AttackVector::SupplyChain, STRONG));
+        m.insert("software supply chain", (AttackVector::SupplyChain, STRONG));
+        m.insert("package hijack", (AttackVector::SupplyChain, STRONG));
+        m.insert("vendor compromise", (AttackVector::SupplyChain, STRONG));
+
+        m.insert("usb", (AttackVector::RemovableMedia, STRONG));
+        m.insert("usb drive", (AttackVector::RemovableMedia, STRONG));
+        m.insert("removable media", (AttackVector::RemovableMedia, STRONG));
+        m.insert("removable drive", (AttackVector::RemovableMedia, STRONG));
+        m.insert("autorun", (AttackVector::RemovableMedia, STRONG));
+
+        m.insert("rdp", (AttackVector::RemoteAccess, STRONG));
+        m.insert("remote desktop", (AttackVector::RemoteAccess, STRONG));
+        m.insert("vpn appliance", (AttackVector::RemoteAccess, STRONG));
+        m.insert("ssh brute", (AttackVector::RemoteAccess, STRONG));
+
+        m.insert("drive by", (AttackVector::DriveByDownload, STRONG));
+        m.insert("drive by download", (AttackVector::DriveByDownload, STRONG));
+        m.insert("exploit kit", (AttackVector::DriveByDownload, STRONG));
+        m.insert("malvertising landing", (AttackVector::DriveByDownload, STRONG));
+
+        m.insert("dns tunneling", (AttackVector::Dns, STRONG));
+        m.insert("dns tunnelling", (AttackVector::Dns, STRONG));
+        m.insert("dns exfiltration", (AttackVector::Dns, STRONG));
+        m.insert("dga", (AttackVector::Dns, STRONG));
+        m.insert("domain generation algorithm", (AttackVector::Dns, STRONG));
+
+        m.insert("malvertising", (AttackVector::Malvertising, STRONG));
+        m.insert("malicious ad", (AttackVector::Malvertising, STRONG));
+        m.insert("malicious ads", (AttackVector::Malvertising, STRONG));
+        m.insert("malicious advertising", (AttackVector::Malvertising, STRONG));
+
+        m.insert("smishing", (AttackVector::Messaging, STRONG));
+        m.insert("sms phishing", (AttackVector::Messaging, STRONG));
+        m.insert("whatsapp scam", (AttackVector::Messaging, STRONG));
+        m.insert("telegram lure", (AttackVector::Messaging, STRONG));
+
+        m
+    })
+}
+
+pub fn urgency_keywords() -> &'static HashMap<&'static str, Urgency> {
+    static KEYWORDS: OnceLock<HashMap<&'static str, Urgency>> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        let mut m = HashMap::new();
+
+        // Hot threats: immediate, active, breaking
+        m.insert("hot", Urgency::Hot);
+        m.insert("immediate", Urgency::Hot);
+        m.insert("active", Urgency::Hot);
+        m.insert("ongoing", Urgency::Hot);
+        m.insert("breaking", Urgency::Hot);
+
+        // Cold threats: old, inactive, stale
+        m.insert("cold", Urgency::Cold);
+        m.insert("stale", Urgency::Cold);
+        m.insert("archived", Urgency::Cold);
+        m.insert("historical", Urgency::Cold);
+        m.insert("retired", Urgency::Cold);
+        m.insert("inactive", Urgency::Cold);
+
+        // Critical threats: high severity, urgent, severe
+        m.insert("critical", Urgency::Critical);
+        m.insert("high", Urgency::Critical);
+        m.insert("severe", Urgency::Critical);
+        m.insert("urgent", Urgency::Critical);
+        m.insert("emergency", Urgency::Critical);
+
+        // Medium threats
+        m.insert("medium", Urgency::Medium);
+        m.insert("moderate", Urgency::Medium);
+        m.insert("average", Urgency::Medium);
+        m.insert("balanced", Urgency::Medium);
+
+        // Low threats: minor, low priority
+        m.insert("low", Urgency::Low);
+        m.insert("minor", Urgency::Low);
+        m.insert("negligible", Urgency::Low);
+        m.insert("low priority", Urgency::Low);
+        m.insert("minimal", Urgency::Low);
+
+        m
+    })
+}
+
+pub fn target_keywords() -> &'static HashMap<&'static str, (Target, f32)> {
+    static KEYWORDS: OnceLock<HashMap<&'static str, (Target, f32)>> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        let mut m = HashMap::new();
+
+        // Web Applications
+        m.insert("webapp", (Target::WebApp, STRONG));
+        m.insert("web app", (Target::WebApp, STRONG));
+        m.insert("website", (Target::WebApp, WEAK));
+        m.insert("web application", (Target::WebApp, STRONG));
+        m.insert("web portal", (Target::WebApp, WEAK));
+        m.insert("online service", (Target::WebApp, WEAK));
+        m.insert("web service", (Target::WebApp, WEAK));
+        m.insert("magecart", (Target::WebApp, STRONG));
+        m.insert("skimmer", (Target::WebApp, STRONG));
+        m.insert("web skimmer", (Target::WebApp, STRONG));
+        m.insert("card skimming", (Target::WebApp, STRONG));
+        m.insert("formjacking", (Target::WebApp, STRONG));
+        m.insert("e skimming", (Target::WebApp, STRONG));
+
+        // Infrastructure
+        m.insert("infrastructure", (Target::Infrastructure, STRONG));
+        m.insert("server", (Target::Infrastructure, WEAK));
+        m.insert("servers", (Target::Infrastructure, WEAK));
+        m.insert("datacenter", (Target::Infrastructure, STRONG));
+        m.insert("data center", (Target::Infrastructure, STRONG));
+        m.insert("network infra", (Target::Infrastructure, STRONG));
+        m.insert("cloud infrastructure", (Target::Infrastructure, STRONG));
+        m.insert("system", (Target::Infrastructure, WEAK));
+        m.insert("backend", (Target::Infrastructure, WEAK));
+
+        // API Abuse
+        m.insert("api abuse", (Target::ApiAbuse, STRONG));
+        m.insert("api exploitation", (Target::ApiAbuse, STRONG));
+        m.insert("api attack", (Target::ApiAbuse, STRONG));
+        m.insert("api misuse", (Target::ApiAbuse, STRONG));
+        m.insert("rest api", (Target::ApiAbuse, STRONG));
+        m.insert("graphql api", (Target::ApiAbuse, STRONG));
+        m.insert("api endpoint", (Target::ApiAbuse, STRONG));
+
+        // IoT Devices
+        m.insert("iot", (Target::IotDevices, STRONG));
+        m.insert("device", (Target::IotDevices, WEAK));
+        m.insert("devices", (Target::IotDevices, WEAK));
+        m.insert("smart devices", (Target::IotDevices, STRONG));
+        m.insert("smart home", (Target::IotDevices, STRONG));
+        m.insert("embedded systems", (Target::IotDevices, STRONG));
+        m.insert("industrial control systems", (Target::IotDevices, STRONG));
+        m.insert("ics", (Target::IotDevices, STRONG));
+        m.insert("plc", (Target::IotDevices, STRONG));
+        m.insert("smart tv", (Target::IotDevices, STRONG));
+        m.insert("iot network", (Target::IotDevices, STRONG));
+
+        // User Focused
+        m.insert("user", (Target::UserFocused, WEAK));
+        m.insert("users", (Target::UserFocused, WEAK));
+        m.insert("human", (Target::UserFocused, WEAK));
+        m.insert("human target", (Target::UserFocused, STRONG));
+        m.insert("social engineering", (Target::UserFocused, STRONG));
+        m.insert("account takeover", (Target::UserFocused, STRONG));
+        m.insert("identity theft", (Target::UserFocused, STRONG));
+        m.insert("credential theft", (Target::UserFocused, STRONG));
+        m.insert("login brute force", (Target::UserFocused, STRONG));
+        m.insert("phishing scam", (Target::UserFocused, STRONG));
+
+        // Email Attacks
+        m.insert("email", (Target::EmailAttack, WEAK));
+        m.insert("email attack", (Target::EmailAttack, STRONG));
+        m.insert("email phishing", (Target::EmailAttack, STRONG));
+        m.insert("email spoofing", (Target::EmailAttack, STRONG));
+        m.insert("spam email", (Target::EmailAttack, STRONG));
+        m.insert("malicious email", (Target::EmailAttack, STRONG));
+        m.insert("email fraud", (Target::EmailAttack, STRONG));
+        m.insert("spearphishing", (Target::EmailAttack, STRONG));
+        m.insert("mail scam", (Target::EmailAttack, STRONG));
+        m.insert("mail fraud", (Target::EmailAttack, STRONG));
+        m.insert("bec", (Target::EmailAttack, STRONG));
+        m.insert("business email compromise", (Target::EmailAttack, STRONG));
+
+        // Mobile Devices
+        m.insert("android", (Target::MobileDevices, STRONG));
+        m.insert("ios", (Target::MobileDevices, STRONG));
+        m.insert("iphone", (Target::MobileDevices, STRONG));
+        m.insert("apk", (Target::MobileDevices, STRONG));
+        m.insert("mobile banking trojan", (Target::MobileDevices, STRONG));
+        m.insert("mobile malware", (Target::MobileDevices, STRONG));
+        m.insert("mobile devices", (Target::MobileDevices, STRONG));
+
+        // Database Systems
+        m.insert("mssql", (Target::DatabaseSystems, STRONG));
+        m.insert("mysql", (Target::DatabaseSystems, STRONG));
+        m.insert("postgresql", (Target::DatabaseSystems, STRONG));
+        m.insert("mongodb", (Target::DatabaseSystems, STRONG));
+        m.insert("database server", (Target::DatabaseSystems, STRONG));
+        m.insert("database servers", (Target::DatabaseSystems, STRONG));
+
+        // Point of Sale
+        m.insert("pos malware", (Target::PointOfSale, STRONG));
+        m.insert("point of sale", (Target::PointOfSale, STRONG));
+        m.insert("payment terminal", (Target::PointOfSale, STRONG));
+        m.insert("payment terminals", (Target::PointOfSale, STRONG));
+
+        m
+    })
+}
+
+
+/// Tag aliases for curated labels that aren't keywords
+
+/// Well-known malware families by the attack type they belong to, keyed by
+/// normalized name. Also used as tag aliases, since OTX often tags a pulse
+/// with the family name alone.
+pub fn malware_family_map() -> HashMap<&'static str, AttackType> {
+    let mut m = HashMap::new();
+
+    m.insert("lockbit", AttackType::Ransomware);
+    m.insert("conti", AttackType::Ransomware);
+    m.insert("blackcat", AttackType::Ransomware);
+    m.insert("alphv", AttackType::Ransomware);
+    m.insert("black basta", AttackType::Ransomware);
+    m.insert("clop", AttackType::Ransomware);
+    m.insert("cl0p", AttackType::Ransomware);
+    m.insert("revil", AttackType::Ransomware);
+    m.insert("sodinokibi", AttackType::Ransomware);
+    m.insert("ryuk", AttackType::Ransomware);
+    m.insert("wannacry", AttackType::Ransomware);
+    m.insert("akira", AttackType::Ransomware);
+
+    m.insert("mirai", AttackType::Botnet);
+    m.insert("mozi", AttackType::Botnet);
+    m.insert("gafgyt", AttackType::Botnet);
+    m.insert("bashlite", AttackType::Botnet);
+
+    m.insert("emotet", AttackType::Trojan);
+    m.insert("trickbot", AttackType::Trojan);
+    m.insert("qakbot", AttackType::Trojan);
+    m.insert("qbot", AttackType::Trojan);
+    m.insert("icedid", AttackType::Trojan);
+
+    m.insert("njrat", AttackType::RemoteAccessTrojan);
+    m.insert("asyncrat", AttackType::RemoteAccessTrojan);
+    m.insert("remcos", AttackType::RemoteAccessTrojan);
+    m.insert("quasar rat", AttackType::RemoteAccessTrojan);
+    m.insert("darkcomet", AttackType::RemoteAccessTrojan);
+    m.insert("nanocore", AttackType::RemoteAccessTrojan);
+
+    m.insert("redline", AttackType::InfoStealer);
+    m.insert("redline stealer", AttackType::InfoStealer);
+    m.insert("raccoon stealer", AttackType::InfoStealer);
+    m.insert("lumma", AttackType::InfoStealer);
+    m.insert("lumma stealer", AttackType::InfoStealer);
+    m.insert("vidar", AttackType::InfoStealer);
+
+    m.insert("agent tesla", AttackType::Spyware);
+    m.insert("formbook", AttackType::Spyware);
+    m.insert("pegasus", AttackType::Spyware);
+
+    m.insert("xmrig", AttackType::Cryptojacking);
+    m.insert("coinhive", AttackType::Cryptojacking);
+
+    m.insert("hermeticwiper", AttackType::Wiper);
+    m.insert("shamoon", AttackType::Wiper);
+    m.insert("whispergate", AttackType::Wiper);
+    m.insert("caddywiper", AttackType::Wiper);
+    m.insert("isaacwiper", AttackType::Wiper);
+    m.insert("acidrain", AttackType::Wiper);
+    m.insert("killdisk", AttackType::Wiper);
+
+    m
+}
+
+/// Looks up a malware family name in `malware_family_map`, ignoring case,
+/// separators and trailing version numbers, so "LockBit 3.0", "lockbit3" and
+/// "AgentTesla" all resolve. Names not in the map fall back to their ending,
+/// see `FAMILY_SUFFIXES`.
+pub fn malware_family_type(family: &str) -> Option<AttackType> {
+    let name = normalize(family);
+    let words: Vec<&str> = name.split(' ').collect();
+    let end = words.iter().rposition(|word| !is_version(word)).map_or(0, |index| index + 1);
+    let base = words[..end].join(" ");
+    let squashed: String = base.chars().filter(|c| c.is_alphanumeric()).collect();
+    let unversioned = squashed.trim_end_matches(|c: char| c.is_ascii_digit());
+    malware_family_map()
+        .into_iter()
+        .find(|(known, _)| {
+            let known_squashed = known.replace(' ', "");
+            *known == base || known_squashed == squashed || known_squashed == unversioned
+        })
+        .map(|(_, attack_type)| attack_type)
+        .or_else(|| family_suffix_type(unversioned))
+}
+
+/// Name endings that give away what an unlisted family does, as in
+/// "DarkLocker" or "ShadowWiper".
+const FAMILY_SUFFIXES: &[(&str, AttackType)] = &[
+    ("locker", AttackType::Ransomware),
+    ("wiper", AttackType::Wiper),
+    ("miner", AttackType::Cryptojacking),
+    ("stealer", AttackType::InfoStealer),
+    ("rat", AttackType::RemoteAccessTrojan),
+];
+
+/// The attack type implied by the ending of a squashed family name.
+fn family_suffix_type(squashed: &str) -> Option<AttackType> {
+    FAMILY_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| squashed.ends_with(suffix))
+        .map(|(_, attack_type)| *attack_type)
+}
+
+/// Most names `normalized_names` keeps, so a pulse with a runaway tag list
+/// can't bloat its records.
+pub const MAX_NAMES: usize = 50;
+
+/// The record's malware family names, see `normalized_names`.
+pub fn malware_families(record: &OTXRecord) -> Vec<String> {
+    normalized_names(&record.malware_families)
+}
+
+/// `names` trimmed, lowercased, deduplicated and sorted, dropping empty
+/// names and keeping the first `MAX_NAMES` in sort order.
+pub fn normalized_names(names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .take(MAX_NAMES)
+        .collect()
+}
+
+/// Whether `word` is a version such as "3", "3.0" or "v2".
+fn is_version(word: &str) -> bool {
+    let digits = word.strip_prefix('v').unwrap_or(word);
+    digits.starts_with(|c: char| c.is_ascii_digit()) && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+pub fn target_tag_aliases() -> HashMap<&'static str, Target> {
+    let mut m = HashMap::new();
+
+    m.insert("iot", Target::IotDevices);
+    m.insert("ics", Target::Infrastructure);
+    m.insert("scada", Target::Infrastructure);
+
+    m
+}
+
+/// Industry names and description keywords for each sector. Plurals are
+/// listed where descriptions use them ("targets hospitals"); industries fall
+/// back to the singular on their own.
+pub fn sector_keywords() -> &'static HashMap<&'static str, Sector> {
+    static KEYWORDS: OnceLock<HashMap<&'static str, Sector>> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        let mut m = HashMap::new();
+
+        m.insert("finance", Sector::Finance);
+        m.insert("financial services", Sector::Finance);
+        m.insert("financial institution", Sector::Finance);
+        m.insert("financial institutions", Sector::Finance);
+        m.insert("banking", Sector::Finance);
+        m.insert("bank", Sector::Finance);
+        m.insert("banks", Sector::Finance);
+        m.insert("insurance", Sector::Finance);
+        m.insert("fintech", Sector::Finance);
+
+        m.insert("healthcare", Sector::Healthcare);
+        m.insert("health care", Sector::Healthcare);
+        m.insert("hospital", Sector::Healthcare);
+        m.insert("hospitals", Sector::Healthcare);
+        m.insert("medical", Sector::Healthcare);
+        m.insert("pharmaceutical", Sector::Healthcare);
+        m.insert("pharmaceuticals", Sector::Healthcare);
+
+        m.insert("government", Sector::Government);
+        m.insert("governments", Sector::Government);
+        m.insert("public sector", Sector::Government);
+        m.insert("military", Sector::Government);
+
+        m.insert("energy", Sector::Energy);
+        m.insert("oil and gas", Sector::Energy);
+        m.insert("utilities", Sector::Energy);
+        m.insert("power grid", Sector::Energy);
+
+        m.insert("education", Sector::Education);
+        m.insert("university", Sector::Education);
+        m.insert("universities", Sector::Education);
+        m.insert("school", Sector::Education);
+        m.insert("schools", Sector::Education);
+        m.insert("academic", Sector::Education);
+
+        m.insert("retail", Sector::Retail);
+        m.insert("retailer", Sector::Retail);
+        m.insert("retailers", Sector::Retail);
+        m.insert("e commerce", Sector::Retail);
+        m.insert("ecommerce", Sector::Retail);
+        m.insert("hospitality", Sector::Retail);
+
+        m.insert("telecom", Sector::Telecom);
+        m.insert("telecoms", Sector::Telecom);
+        m.insert("telecommunication", Sector::Telecom);
+        m.insert("telecommunications", Sector::Telecom);
+        m.insert("telco", Sector::Telecom);
+        m.insert("telcos", Sector::Telecom);
+
+        m.insert("manufacturing", Sector::Manufacturing);
+        m.insert("manufacturer", Sector::Manufacturing);
+        m.insert("manufacturers", Sector::Manufacturing);
+        m.insert("automotive", Sector::Manufacturing);
+
+        m.insert("technology", Sector::Technology);
+        m.insert("information technology", Sector::Technology);
+        m.insert("tech companies", Sector::Technology);
+
+        m
+    })
+}
+
+/// OS keywords. "os" alone is deliberately absent, it is part of too many
+/// unrelated words and names.
+pub fn os_target_keywords() -> &'static HashMap<&'static str, OsTarget> {
+    static KEYWORDS: OnceLock<HashMap<&'static str, OsTarget>> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        let mut m = HashMap::new();
+
+        m.insert("windows", OsTarget::Windows);
+        m.insert("win32", OsTarget::Windows);
+        m.insert("win64", OsTarget::Windows);
+        m.insert("powershell", OsTarget::Windows);
+        m.insert("active directory", OsTarget::Windows);
+
+        m.insert("linux", OsTarget::Linux);
+        m.insert("elf", OsTarget::Linux);
+        m.insert("elf binary", OsTarget::Linux);
+
+        m.insert("macos", OsTarget::MacOS);
+        m.insert("mac os", OsTarget::MacOS);
+        m.insert("osx", OsTarget::MacOS);
+        m.insert("os x", OsTarget::MacOS);
+        m.insert("mach o", OsTarget::MacOS);
+
+        m.insert("android", OsTarget::Android);
+        m.insert("apk", OsTarget::Android);
+
+        m.insert("ios", OsTarget::Ios);
+        m.insert("iphone", OsTarget::Ios);
+        m.insert("ipad", OsTarget::Ios);
+
+        m.insert("router", OsTarget::Embedded);
+        m.insert("routers", OsTarget::Embedded);
+        m.insert("firmware", OsTarget::Embedded);
+        m.insert("network appliance", OsTarget::Embedded);
+        m.insert("network appliances", OsTarget::Embedded);
+        m.insert("busybox", OsTarget::Embedded);
+
+        m
+    })
+}
+
+/// Threat actor aliases
+
+/// Names and vendor aliases of well-known APT groups, normalized, mapped to
+/// one canonical name per group.
+pub fn threat_actor_aliases() -> HashMap<&'static str, &'static str> {
+    let mut m = HashMap::new();
+
+    m.insert("apt28", "APT28");
+    m.insert("fancy bear", "APT28");
+    m.insert("sofacy", "APT28");
+    m.insert("strontium", "APT28");
+    m.insert("forest blizzard", "APT28");
+    m.insert("pawn storm", "APT28");
+
+    m.insert("apt29", "APT29");
+    m.insert("cozy bear", "APT29");
+    m.insert("nobelium", "APT29");
+    m.insert("midnight blizzard", "APT29");
+    m.insert("the dukes", "APT29");
+
+    m.insert("sandworm", "Sandworm");
+    m.insert("voodoo bear", "Sandworm");
+    m.insert("seashell blizzard", "Sandworm");
+    m.insert("apt44", "Sandworm");
+
+    m.insert("turla", "Turla");
+    m.insert("venomous bear", "Turla");
+    m.insert("snake", "Turla");
+
+    m.insert("lazarus", "Lazarus Group");
+    m.insert("lazarus group", "Lazarus Group");
+    m.insert("hidden cobra", "Lazarus Group");
+    m.insert("diamond sleet", "Lazarus Group");
+
+    m.insert("kimsuky", "Kimsuky");
+    m.insert("velvet chollima", "Kimsuky");
+    m.insert("apt43", "Kimsuky");
+
+    m.insert("apt41", "APT41");
+    m.insert("double dragon", "APT41");
+    m.insert("barium", "APT41");
+
+    m.insert("apt33", "APT33");
+    m.insert("elfin", "APT33");
+    m.insert("peach sandstorm", "APT33");
+
+    m.insert("apt35", "APT35");
+    m.insert("charming kitten", "APT35");
+    m.insert("phosphorus", "APT35");
+
+    m
+}
+
+/// MITRE ATT&CK lookup tables
+
+/// Common enterprise techniques by ID, with their name and primary tactic.
+pub fn mitre_technique_table() -> HashMap<&'static str, (&'static str, &'static str)> {
+    let mut m = HashMap::new();
+
+    m.insert("T1595", ("Active Scanning", "Reconnaissance"));
+
+    m.insert("T1566", ("Phishing", "Initial Access"));
+    m.insert("T1566.001", ("Spearphishing Attachment", "Initial Access"));
+    m.insert("T1566.002", ("Spearphishing Link", "Initial Access"));
+    m.insert("T1190", ("Exploit Public-Facing Application", "Initial Access"));
+    m.insert("T1133", ("External Remote Services", "Initial Access"));
+    m.insert("T1078", ("Valid Accounts", "Initial Access"));
+
+    m.insert("T1059", ("Command and Scripting Interpreter", "Execution"));
+    m.insert("T1059.001", ("PowerShell", "Execution"));
+    m.insert("T1204", ("User Execution", "Execution"));
+
+    m.insert("T1053", ("Scheduled Task/Job", "Persistence"));
+    m.insert("T1547", ("Boot or Logon Autostart Execution", "Persistence"));
+
+    m.insert("T1055", ("Process Injection", "Privilege Escalation"));
+    m.insert("T1068", ("Exploitation for Privilege Escalation", "Privilege Escalation"));
+
+    m.insert("T1027", ("Obfuscated Files or Information", "Defense Evasion"));
+    m.insert("T1562", ("Impair Defenses", "Defense Evasion"));
+
+    m.insert("T1003", ("OS Credential Dumping", "Credential Access"));
+    m.insert("T1110", ("Brute Force", "Credential Access"));
+
+    m.insert("T1082", ("System Information Discovery", "Discovery"));
+
+    m.insert("T1021", ("Remote Services", "Lateral Movement"));
+
+    m.insert("T1005", ("Data from Local System", "Collection"));
+
+    m.insert("T1071", ("Application Layer Protocol", "Command and Control"));
+    m.insert("T1105", ("Ingress Tool Transfer", "Command and Control"));
+
+    m.insert("T1041", ("Exfiltration Over C2 Channel", "Exfiltration"));
+
+    m.insert("T1486", ("Data Encrypted for Impact", "Impact"));
+    m.insert("T1490", ("Inhibit System Recovery", "Impact"));
+    m.insert("T1496", ("Resource Hijacking", "Impact"));
+    m.insert("T1498", ("Network Denial of Service", "Impact"));
+    m.insert("T1499", ("Endpoint Denial of Service", "Impact"));
+
+    m
+}
+
+/// Techniques specific enough to classify a record's attack type on their
+/// own. Sub-techniques inherit their parent's entry.
+pub fn technique_attack_types() -> HashMap<&'static str, AttackType> {
+    let mut m = HashMap::new();
+
+    m.insert("T1566", AttackType::Phishing);
+    m.insert("T1486", AttackType::Ransomware);
+    m.insert("T1498", AttackType::Ddos);
+    m.insert("T1499", AttackType::Ddos);
+    m.insert("T1110", AttackType::BruteForce);
+    m.insert("T1496", AttackType::Cryptojacking);
+    m.insert("T1485", AttackType::Wiper);
+    m.insert("T1561", AttackType::Wiper);
+    m.insert("T1190", AttackType::Exploit);
+    m.insert("T1595", AttackType::Reconnaissance);
+
+    m
+}
+
+/// Regex rules for identifiers keywords can't capture
+
+/// Ransom demands are paid to a wallet, so a Bitcoin address points at
+/// ransomware. A bech32 address is distinctive enough on its own; a legacy
+/// base58 one is matched case-sensitively and only weakly, since plenty of
+/// other tokens fit its shape. Numbered APT group names such as "APT28" name
+/// a nation-state actor.
+pub fn attack_type_patterns() -> Vec<(Rule, (AttackType, f32))> {
+    vec![
+        (Rule::Pattern(r"bc1[02-9ac-hj-np-z]{11,71}"), (AttackType::Ransomware, STRONG)),
+        (Rule::Pattern(r"(?-i:[13][a-km-zA-HJ-NP-Z1-9]{25,34})"), (AttackType::Ransomware, WEAK)),
+        (Rule::Pattern(r"apt ?\d{1,3}"), (AttackType::APT, STRONG)),
+    ]
+}
+
+pub fn attack_vector_patterns() -> Vec<(Rule, (AttackVector, f32))> {
+    vec![]
+}
+
+/// A CVE means a known vulnerability is being exploited and a MITRE ATT&CK
+/// technique ID means documented adversary tradecraft; either makes a record
+/// at least medium severity.
+pub fn urgency_patterns() -> Vec<(Rule, (Urgency, f32))> {
+    vec![
+        (Rule::Pattern(r"cve \d{4} \d{4,7}"), (Urgency::Medium, STRONG)),
+        (Rule::Pattern(r"t1\d{3}(?:\.\d{3})?"), (Urgency::Medium, STRONG)),
+    ]
+}
+
+pub fn target_patterns() -> Vec<(Rule, (Target, f32))> {
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    const THRESHOLD: f32 = DEFAULT_CLASSIFICATION_THRESHOLD;
+
+    fn record(name: &str, description: &str, tags: &[&str]) -> OTXRecord {
+        OTXRecord {
+            id: "pulse-1".to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            author_name: "tester".to_string(),
+            modified: "2024-01-01T00:00:00".to_string(),
+            created: "2024-01-01T00:00:00".to_string(),
+            revision: 1,
+            tlp: Tlp::Clear,
+            public: 1,
+            adversary: "".to_string(),
+            indicators: vec![],
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            targeted_countries: vec![],
+            malware_families: vec![],
+            attack_ids: vec![],
+            references: vec![],
+            industries: vec![],
+            extract_source: vec![],
+            more_indicators: false,
+        }
+    }
+
+    fn pulse(results: Vec<OTXRecord>) -> OTXPulse {
+        OTXPulse::from(results)
+    }
+
+    fn run_array_map(pulse: &OTXPulse) -> Vec<(Option<RecordData>, RecordData)> {
+        let value = serde_json::to_vec(pulse).unwrap();
+        array_map(&SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0)).unwrap()
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> SmartModuleExtraParams {
+        let params: std::collections::BTreeMap<String, String> = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        SmartModuleExtraParams::from(params)
+    }
+
+    fn seconds_between(earlier: &str, later: &str) -> u64 {
+        let earlier: SystemTime = parse_iso8601(earlier).unwrap();
+        let later: SystemTime = parse_iso8601(later).unwrap();
+        later.duration_since(earlier).unwrap().as_secs()
+    }
+
+    #[test]
+    fn parse_january_dates() {
+        assert_eq!(seconds_between("2023-01-01T00:00:00", "2023-01-31T00:00:00"), 30 * 86_400);
+        assert_eq!(seconds_between("2023-01-31T00:00:00", "2023-02-01T00:00:00"), 86_400);
+    }
+
+    #[test]
+    fn parse_31_day_month() {
+        assert_eq!(seconds_between("2023-03-30T00:00:00", "2023-03-31T00:00:00"), 86_400);
+        assert_eq!(seconds_between("2023-03-31T00:00:00", "2023-04-01T00:00:00"), 86_400);
+        assert_eq!(seconds_between("2023-07-01T00:00:00", "2023-08-01T00:00:00"), 31 * 86_400);
+    }
+
+    #[test]
+    fn parse_december_dates() {
+        assert_eq!(
+            seconds_between("2023-01-01T00:00:00", "2023-12-31T23:59:59"),
+            364 * 86_400 + 86_399
+        );
+        assert_eq!(seconds_between("2023-11-30T00:00:00", "2023-12-01T00:00:00"), 86_400);
+    }
+
+    #[test]
+    fn parse_then_format_round_trips() {
+        for date in [
+            "2023-01-01T00:00:00",
+            "2023-01-31T12:30:45",
+            "2023-02-28T23:59:59",
+            "2023-03-31T00:00:00",
+            "2023-08-31T08:15:00",
+            "2023-12-31T23:59:59",
+        ] {
+            assert_eq!(format_system_time(parse_iso8601(date).unwrap()), date);
+        }
+    }
+
+    #[test]
+    fn windows_does_not_trigger_dos() {
+        let rec = record("", "Windows infostealer campaign", &[]);
+        let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+        assert!(!types.contains(&AttackType::Ddos));
+        assert!(types.contains(&AttackType::InfoStealer));
+
+        let rec = record("", "Incorrect dosage reported by endpoint tools", &[]);
+        assert!(!labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)).contains(&AttackType::Ddos));
+    }
+
+    #[test]
+    fn dos_still_matches_as_a_word() {
+        let rec = record("DoS attack on gateways", "", &[]);
+        assert!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)).contains(&AttackType::Ddos));
+
+        let rec = record("Windows server", "", &[]);
+        assert!(!labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)).contains(&AttackType::Ddos));
+    }
+
+    #[test]
+    fn contains_word_respects_boundaries() {
+        assert!(!contains_word("windows server", "dos"));
+        assert!(contains_word("dos attack", "dos"));
+        assert!(contains_word("(dos)", "dos"));
+        assert!(contains_word("a distributed denial of service flood", "denial of service"));
+        assert!(!contains_word("robotics", "bot"));
+        assert!(contains_word("windows dos", "dos"));
+    }
+
+    #[test]
+    fn high_and_low_require_word_boundaries() {
+        let rec = record("", "Report highlights a new loader", &[]);
+        let severity = classify_urgency(&FlattenedText::new(&rec), config()).severity;
+        assert_eq!(severity, Severity::Unknown);
+
+        let rec = record("", "Critical flaw follows the slow rollout", &[]);
+        let severity = classify_urgency(&FlattenedText::new(&rec), config()).severity;
+        assert_eq!(severity, Severity::Critical);
+    }
+
+    #[test]
+    fn bot_web_and_user_require_word_boundaries() {
+        let rec = record("", "Robotics firm reports bottleneck in webhook for username lookups", &[]);
+        let text = FlattenedText::new(&rec);
+        assert!(!labels(&classify_attack_types(&text, THRESHOLD)).contains(&AttackType::Botnet));
+        assert_eq!(labels(&classify_attack_vectors(&text, THRESHOLD)), vec![AttackVector::Unknown]);
+        assert_eq!(labels(&classify_targets(&text, THRESHOLD)), vec![Target::Unknown]);
+    }
+
+    #[test]
+    fn multi_word_keywords_match_across_a_space() {
+        let rec = record("", "Blind SQL injection against the web application", &[]);
+        let text = FlattenedText::new(&rec);
+        assert!(labels(&classify_attack_types(&text, THRESHOLD)).contains(&AttackType::SQLInjection));
+        assert!(labels(&classify_targets(&text, THRESHOLD)).contains(&Target::WebApp));
+    }
+
+    fn per_keyword_scan<T: Copy + PartialEq>(keywords: &HashMap<&'static str, (T, f32)>, text: &str) -> Vec<T> {
+        let mut found: Vec<T> = vec![];
+        for (keyword, (label, _)) in keywords.iter() {
+            if contains_word(text, keyword) && !found.contains(label) {
+                found.push(*label);
+            }
+        }
+        found
+    }
+
+    fn same_labels<T: PartialEq + std::fmt::Debug>(a: &[T], b: &[T]) {
+        assert_eq!(a.len(), b.len(), "{:?} vs {:?}", a, b);
+        assert!(a.iter().all(|label| b.contains(label)), "{:?} vs {:?}", a, b);
+    }
+
+    #[test]
+    fn matcher_agrees_with_per_keyword_scan() {
+        let texts = [
+            "windows infostealer campaign drops a backdoor via spearphishing email",
+            "ddos botnet with c&c over aws s3 buckets and rest api abuse",
+            "blind sql injection against the web portal, then ransomware on servers",
+            "smart tv and plc devices hit by a syn flood",
+            "nothing interesting here",
+        ];
+        for text in texts {
+            same_labels(
+                &attack_type_matcher().find(&[text]),
+                &per_keyword_scan(attack_type_keywords(), text),
+            );
+            same_labels(
+                &attack_vector_matcher().find(&[text]),
+                &per_keyword_scan(attack_vector_keywords(), text),
+            );
+            same_labels(
+                &target_matcher().find(&[text]),
+                &per_keyword_scan(target_keywords(), text),
+            );
+        }
+    }
+
+    #[test]
+    fn matchers_are_built_once() {
+        assert!(std::ptr::eq(attack_type_matcher(), attack_type_matcher()));
+        assert!(std::ptr::eq(target_matcher(), target_matcher()));
+    }
+
+    #[test]
+    fn keyword_tables_are_built_once() {
+        assert!(std::ptr::eq(attack_type_keywords(), attack_type_keywords()));
+        assert!(std::ptr::eq(attack_vector_keywords(), attack_vector_keywords()));
+        assert!(std::ptr::eq(urgency_keywords(), urgency_keywords()));
+        assert!(std::ptr::eq(target_keywords(), target_keywords()));
+        assert!(std::ptr::eq(sector_keywords(), sector_keywords()));
+    }
+
+    fn indicator(title: &str, description: &str, role: Option<&str>, is_active: u8) -> OTXIndicator {
+        OTXIndicator {
+            id: 1,
+            indicator: "198.51.100.7".to_string(),
+            type_: "FileHash-SHA256".to_string(),
+            created: "2024-01-01T00:00:00".to_string(),
+            content: "".to_string(),
+            title: title.to_string(),
+            description: description.to_string(),
+            expiration: None,
+            is_active,
+            role: role.map(str::to_string),
+        }
+    }
+
+    /// A pulse with 50 indicators classified in one pass over its flattened
+    /// text finds the same labels as scanning its joined text once per keyword.
+    #[test]
+    fn fifty_indicator_pulse_matches_per_keyword_scan() {
+        let titles = [
+            ("Ransomware dropper", "Encrypts file shares on windows servers"),
+            ("C&C server", "Botnet command and control over a rest api"),
+            ("Phishing kit", "Credential harvesting page sent by email"),
+            ("Scanner", "Brute force against ssh on linux hosts"),
+            ("Miner", "Cryptojacking payload abusing aws accounts"),
+        ];
+        let mut rec = record("Mass campaign", "Several clusters of activity", &["Botnet"]);
+        rec.indicators = (0..50u128)
+            .map(|id| {
+                let (title, description) = titles[id as usize % titles.len()];
+                let mut ind = indicator(title, description, Some("malware_hosting"), (id % 2) as u8);
+                ind.id = id;
+                ind
+            })
+            .collect();
+
+        let text = FlattenedText::new(&rec);
+        let attack_types = attack_type_matcher().find(&text.texts(ATTACK_TYPE_SCOPE));
+        let attack_vectors = attack_vector_matcher().find(&text.texts(ATTACK_VECTOR_SCOPE));
+        let targets = target_matcher().find(&text.texts(TARGET_SCOPE));
+
+        same_labels(&attack_types, &per_keyword_scan(attack_type_keywords(), &joined_text(&rec, true, true)));
+        same_labels(&attack_vectors, &per_keyword_scan(attack_vector_keywords(), &joined_text(&rec, true, false)));
+        same_labels(&targets, &per_keyword_scan(target_keywords(), &joined_text(&rec, true, false)));
+        for expected in [AttackType::Ransomware, AttackType::Botnet, AttackType::Phishing, AttackType::BruteForce, AttackType::Cryptojacking] {
+            assert!(attack_types.contains(&expected), "{:?} missing from {:?}", expected, attack_types);
+        }
+    }
+
+    /// The per-classifier text each classifier used to build for itself.
+    fn joined_text(record: &OTXRecord, with_indicators: bool, with_roles: bool) -> String {
+        let mut all_text = vec![record.name.as_str(), record.description.as_str()];
+        all_text.extend(record.tags.iter().map(String::as_str));
+        if with_indicators {
+            for ind in &record.indicators {
+                all_text.push(ind.title.as_str());
+                all_text.push(ind.description.as_str());
+                if with_roles {
+                    all_text.push(ind.role.as_deref().unwrap_or(""));
+                }
+            }
+        }
+        all_text.join(" ").to_lowercase()
+    }
+
+    fn fixture_records() -> Vec<OTXRecord> {
+        let mut botnet = record("Mirai variant", "DDoS botnet hitting IoT cameras", &["Botnet", "IoT"]);
+        botnet.indicators = vec![
+            indicator("C&C server", "Command and control for the botnet", Some("command_and_control"), 1),
+            indicator("Loader", "", None, 0),
+        ];
+        let mut phishing = record("Invoice lure", "Spearphishing email targeting users", &["phishing"]);
+        phishing.indicators = vec![indicator("Credential harvesting page", "Web portal clone", Some("phishing"), 1)];
+        let mut quiet = record("Weekly roundup", "Minor activity observed", &[]);
+        quiet.indicators = vec![indicator("", "", Some("scanning_host"), 0)];
+        vec![
+            botnet,
+            phishing,
+            quiet,
+            record("Critical SQLi", "Blind SQL injection against REST API endpoints on AWS", &["High"]),
+        ]
+    }
+
+    #[test]
+    fn flattened_text_matches_per_classifier_text() {
+        for rec in fixture_records() {
+            let text = FlattenedText::new(&rec);
+            let legacy_types = attack_type_matcher().find(&[&joined_text(&rec, true, true)]);
+            let legacy_vectors = attack_vector_matcher().find(&[&joined_text(&rec, true, false)]);
+            let legacy_targets = target_matcher().find(&[&joined_text(&rec, true, false)]);
+
+            same_labels(&attack_type_matcher().find(&text.texts(ATTACK_TYPE_SCOPE)), &legacy_types);
+            same_labels(&attack_vector_matcher().find(&text.texts(ATTACK_VECTOR_SCOPE)), &legacy_vectors);
+            same_labels(&target_matcher().find(&text.texts(TARGET_SCOPE)), &legacy_targets);
+            assert_eq!(text.texts(URGENCY_SCOPE).join(" ").to_lowercase(), joined_text(&rec, false, false));
+        }
+    }
+
+    #[test]
+    fn flattened_text_classifies_fixtures() {
+        let records = fixture_records();
+
+        let botnet = FlattenedText::new(&records[0]);
+        same_labels(&labels(&classify_attack_types(&botnet, THRESHOLD)), &[AttackType::Ddos, AttackType::Botnet]);
+        same_labels(&labels(&classify_attack_vectors(&botnet, THRESHOLD)), &[AttackVector::Network]);
+        same_labels(&labels(&classify_targets(&botnet, THRESHOLD)), &[Target::IotDevices]);
+        assert_eq!(classify_urgency(&botnet, config()), UrgencyAssessment { temperature: Temperature::Cold, severity: Severity::Low });
+
+        let phishing = FlattenedText::new(&records[1]);
+        same_labels(&labels(&classify_attack_types(&phishing, THRESHOLD)), &[AttackType::Phishing]);
+        same_labels(&labels(&classify_attack_vectors(&phishing, THRESHOLD)), &[AttackVector::Email]);
+        same_labels(&labels(&classify_targets(&phishing, THRESHOLD)), &[Target::EmailAttack]);
+        assert_eq!(classify_urgency(&phishing, config()), UrgencyAssessment { temperature: Temperature::Cold, severity: Severity::Low });
+
+        let quiet = FlattenedText::new(&records[2]);
+        assert_eq!(labels(&classify_attack_types(&quiet, THRESHOLD)), vec![AttackType::Unknown]);
+        assert_eq!(labels(&classify_attack_vectors(&quiet, THRESHOLD)), vec![AttackVector::Unknown]);
+        assert_eq!(labels(&classify_targets(&quiet, THRESHOLD)), vec![Target::Unknown]);
+        assert_eq!(classify_urgency(&quiet, config()), UrgencyAssessment { temperature: Temperature::Cold, severity: Severity::Low });
+    }
+
+    #[test]
+    fn parse_accepts_trailing_z() {
+        assert_eq!(
+            parse_iso8601("2024-01-01T12:00:00Z").unwrap(),
+            parse_iso8601("2024-01-01T12:00:00").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_normalizes_offsets_to_utc() {
+        assert_eq!(
+            parse_iso8601("2024-01-01T12:00:00+02:00").unwrap(),
+            parse_iso8601("2024-01-01T10:00:00").unwrap()
+        );
+        assert_eq!(
+            parse_iso8601("2024-01-01T12:00:00-05:30").unwrap(),
+            parse_iso8601("2024-01-01T17:30:00").unwrap()
+        );
+        assert_eq!(
+            parse_iso8601("2024-01-01T00:30:00.250+0100").unwrap(),
+            parse_iso8601("2023-12-31T23:30:00").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_treats_naive_times_as_utc() {
+        let parsed = parse_iso8601("2024-01-01T12:00:00").unwrap();
+        assert_eq!(parsed.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs(), 1_704_110_400);
+        assert!(parse_iso8601("2024-01-01T12:00:00+2").is_err());
+    }
+
+    #[test]
+    fn latest_expiration_compares_in_utc() {
+        let mut rec = record("", "", &[]);
+        let mut early = indicator("", "", None, 1);
+        early.expiration = Some("2024-06-01T12:00:00+05:00".to_string());
+        let mut late = indicator("", "", None, 1);
+        late.expiration = Some("2024-06-01T09:00:00Z".to_string());
+        rec.indicators = vec![late, early];
+
+        assert_eq!(get_expiration(&rec).unwrap(), "2024-06-01T09:00:00");
+    }
+
+    #[test]
+    fn evidence_points_at_matching_tag() {
+        let rec = record("Weekly roundup", "Activity observed", &["roundup", "Ransomware"]);
+        let evidence = collect_evidence(&FlattenedText::new(&rec), THRESHOLD);
+
+        assert_eq!(
+            evidence,
+            vec![Evidence {
+                classification: Classification::AttackType(AttackType::Ransomware),
+                keyword: "ransomware".to_string(),
+                source: TextSource::Tag(1),
+                exact_tag: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn evidence_respects_classifier_scopes() {
+        let mut rec = record("", "", &[]);
+        let mut ind = indicator("Phishing kit", "", Some("critical"), 1);
+        ind.id = 42;
+        rec.indicators = vec![ind];
+        let evidence = collect_evidence(&FlattenedText::new(&rec), THRESHOLD);
+
+        assert!(evidence.iter().any(|hit| hit.source == TextSource::Indicator(42)
+            && hit.classification == Classification::AttackType(AttackType::Phishing)));
+        assert!(evidence.iter().all(|hit| !matches!(hit.classification, Classification::Urgency(_))));
+    }
+
+    #[test]
+    fn evidence_is_omitted_unless_enabled() {
+        let rec = record("", "ransomware", &[]);
+        let text = FlattenedText::new(&rec);
+        let mut enriched = EnrichedThreatRecord {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            source: DEFAULT_SOURCE.to_string(),
+            feed_url: None,
+            partial_feed: false,
+            source_id: rec.id.clone(),
+            source_name: rec.name.clone(),
+            pulse_id: rec.id.clone(),
+            pulse_name: rec.name.clone(),
+            revision: rec.revision,
+            created: rec.created.clone(),
+            modified: rec.modified.clone(),
+            age_hours: None,
+            author: rec.author_name.clone(),
+            attack_types: classify_attack_types(&text, THRESHOLD),
+            attack_type_scores: classify_attack_types_scored(&text),
+            attack_vectors: classify_attack_vectors(&text, THRESHOLD),
+            attack_vector_matches: vec![],
+            dominant_vector: AttackVector::Unknown,
+            urgency: classify_urgency(&text, config()),
+            urgency_score: classify_urgency_score(&rec, classify_urgency(&text, config())),
+            threat_score: 0,
+            overall_severity: "Info".to_string(),
+            tlp: rec.tlp,
+            restricted: rec.tlp.is_restricted(),
+            targets: classify_targets(&text, THRESHOLD),
+            os_targets: vec![OsTarget::Unknown],
+            sectors: vec![],
+            raw_industries: vec![],
+            industries: vec![],
+            locations: vec!["Unknown".to_string()],
+            malware_families: vec![],
+            tags: vec![],
+            cve_ids: vec![],
+            cvss_score: None,
+            mitre_techniques: vec![],
+            mitre_tactics: vec![],
+            adversary: None,
+            threat_actor: None,
+            iocs: vec![],
+            references: vec![],
+            active_indicator_count: 0,
+            expired_indicator_count: 0,
+            expiration_date: "".to_string(),
+            earliest_expiration: "".to_string(),
+            evidence: None,
+            hits: vec![],
+            dropped_references: None,
+            truncated: false,
+        };
+        assert!(!serde_json::to_string(&enriched).unwrap().contains("evidence"));
+
+        enriched.evidence = Some(collect_evidence(&text, THRESHOLD));
+        let json = serde_json::to_value(&enriched).unwrap();
+        assert_eq!(json["evidence"][0]["source"], "Description");
+        assert_eq!(json["evidence"][0]["keyword"], "ransomware");
+    }
+
+    #[test]
+    fn config_reads_evidence_param() {
+        assert!(!EnricherConfig::from_params(&params(&[])).unwrap().evidence);
+        assert!(EnricherConfig::from_params(&params(&[("evidence", "true")])).unwrap().evidence);
+        assert!(EnricherConfig::from_params(&params(&[("evidence", "yes please")])).is_err());
+    }
+
+    #[test]
+    fn parse_reports_typed_errors() {
+        assert_eq!(parse_iso8601("2024-13-01T00:00:00"), Err(DateParseError::InvalidMonth(13)));
+        assert_eq!(parse_iso8601("2024-00-01T00:00:00"), Err(DateParseError::InvalidMonth(0)));
+        assert_eq!(parse_iso8601("garbage"), Err(DateParseError::TooFewFields));
+        assert_eq!(parse_iso8601("20x4-01-01T00:00:00"), Err(DateParseError::InvalidYear));
+        assert_eq!(parse_iso8601("2024-01-01Tab:00:00"), Err(DateParseError::InvalidField("hour")));
+        assert_eq!(parse_iso8601("2024-01-01T00:00:00+2"), Err(DateParseError::InvalidOffset));
+        assert_eq!(parse_iso8601("2024-01-01T00:00:00+1é1"), Err(DateParseError::InvalidOffset));
+        assert_eq!(parse_iso8601("1969-12-31T23:59:59"), Err(DateParseError::OutOfRange));
+        assert_eq!(parse_iso8601("10000-01-01T00:00:00"), Err(DateParseError::OutOfRange));
+        assert_eq!(parse_iso8601("999999999999-01-01T00:00:00"), Err(DateParseError::OutOfRange));
+        assert!(parse_iso8601("9999-12-31T23:59:59Z").is_ok());
+    }
+
+    #[test]
+    fn date_parse_error_works_as_boxed_error() {
+        let boxed: Box<dyn std::error::Error> = parse_iso8601("garbage").unwrap_err().into();
+        assert_eq!(boxed.to_string(), "Invalid date format");
+    }
+
+    fn confidence_of<T: PartialEq>(scored: &[Scored<T>], label: T) -> f32 {
+        scored.iter().find(|scored| scored.label == label).unwrap().confidence
+    }
+
+    #[test]
+    fn repeated_hits_raise_confidence() {
+        let mut heavy = record("", "", &[]);
+        heavy.indicators = (0..15).map(|_| indicator("Phishing page", "", None, 1)).collect();
+        let mut light = record("", "", &[]);
+        light.indicators = vec![indicator("Sent by email", "", None, 1)];
+
+        let heavy = confidence_of(&classify_attack_vectors(&FlattenedText::new(&heavy), THRESHOLD), AttackVector::Email);
+        let light = confidence_of(&classify_attack_vectors(&FlattenedText::new(&light), THRESHOLD), AttackVector::Email);
+        assert!(heavy > light, "{} <= {}", heavy, light);
+        assert!(heavy <= 1.0);
+    }
+
+    #[test]
+    fn name_and_tag_hits_outweigh_indicator_hits() {
+        let named = record("Ransomware wave", "", &[]);
+        let tagged = record("", "", &["ransomware wave"]);
+        let exact = record("", "", &["ransomware"]);
+        let mut buried = record("", "", &[]);
+        buried.indicators = vec![indicator("", "Drops ransomware", None, 1)];
+
+        let named = confidence_of(&classify_attack_types(&FlattenedText::new(&named), THRESHOLD), AttackType::Ransomware);
+        let tagged = confidence_of(&classify_attack_types(&FlattenedText::new(&tagged), THRESHOLD), AttackType::Ransomware);
+        let exact = confidence_of(&classify_attack_types(&FlattenedText::new(&exact), THRESHOLD), AttackType::Ransomware);
+        let buried = confidence_of(&classify_attack_types(&FlattenedText::new(&buried), THRESHOLD), AttackType::Ransomware);
+        assert_eq!(named, tagged);
+        assert!(exact > named, "{} <= {}", exact, named);
+        assert!(named > buried, "{} <= {}", named, buried);
+    }
+
+    #[test]
+    fn distinct_keywords_raise_confidence() {
+        let repeated = record("", "ransomware ransomware", &[]);
+        let distinct = record("", "ransomware encryptor", &[]);
+
+        let repeated = confidence_of(&classify_attack_types(&FlattenedText::new(&repeated), THRESHOLD), AttackType::Ransomware);
+        let distinct = confidence_of(&classify_attack_types(&FlattenedText::new(&distinct), THRESHOLD), AttackType::Ransomware);
+        assert!(distinct > repeated, "{} <= {}", distinct, repeated);
+    }
+
+    #[test]
+    fn unknown_fallback_has_zero_confidence() {
+        let rec = record("Weekly roundup", "", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(classify_attack_types(&text, THRESHOLD), vec![Scored { label: AttackType::Unknown, confidence: 0.0 }]);
+        assert_eq!(classify_attack_vectors(&text, THRESHOLD), vec![Scored { label: AttackVector::Unknown, confidence: 0.0 }]);
+        assert_eq!(classify_targets(&text, THRESHOLD), vec![Scored { label: Target::Unknown, confidence: 0.0 }]);
+    }
+
+    #[test]
+    fn scored_labels_serialize_as_objects() {
+        let json = serde_json::to_value(Scored { label: AttackType::Phishing, confidence: 0.5 }).unwrap();
+        assert_eq!(json, serde_json::json!({ "label": "Phishing", "confidence": 0.5 }));
+    }
+
+    #[test]
+    fn parse_rejects_out_of_range_fields() {
+        assert_eq!(parse_iso8601("2024-13-01T00:00:00"), Err(DateParseError::InvalidMonth(13)));
+        assert_eq!(parse_iso8601("2024-02-30T00:00:00"), Err(DateParseError::InvalidDay(30)));
+        assert_eq!(parse_iso8601("2023-02-29T00:00:00"), Err(DateParseError::InvalidDay(29)));
+        assert_eq!(parse_iso8601("2024-04-00T00:00:00"), Err(DateParseError::InvalidDay(0)));
+        assert_eq!(parse_iso8601("2024-01-01T25:00:00"), Err(DateParseError::InvalidHour(25)));
+        assert_eq!(parse_iso8601("2024-01-01T00:60:00"), Err(DateParseError::InvalidMinute(60)));
+        assert_eq!(parse_iso8601("2024-01-01T00:00:61"), Err(DateParseError::InvalidSecond(61)));
+        assert!(parse_iso8601("2016-12-31T23:59:60").is_ok());
+    }
+
+    #[test]
+    fn latest_expiration_skips_invalid_dates() {
+        let mut rec = record("", "", &[]);
+        let mut valid = indicator("", "", None, 1);
+        valid.expiration = Some("2024-06-01T00:00:00".to_string());
+        let mut bogus = indicator("", "", None, 1);
+        bogus.expiration = Some("2024-13-40T00:00:00".to_string());
+        rec.indicators = vec![valid, bogus];
+
+        assert_eq!(get_expiration(&rec).unwrap(), "2024-06-01T00:00:00");
+    }
+
+    #[test]
+    fn earliest_and_latest_expirations() {
+        let mut rec = record("", "", &[]);
+        rec.indicators = ["2024-06-01T00:00:00", "2023-11-15T08:00:00", "2025-01-31T23:59:59"]
+            .iter()
+            .map(|expiration| {
+                let mut ind = indicator("", "", None, 1);
+                ind.expiration = Some(expiration.to_string());
+                ind
+            })
+            .collect();
+        let mut unparseable = indicator("", "", None, 1);
+        unparseable.expiration = Some("not a date".to_string());
+        rec.indicators.push(unparseable);
+        rec.indicators.push(indicator("", "", None, 1));
+
+        assert_eq!(get_earliest_expiration(&rec).unwrap(), "2023-11-15T08:00:00");
+        assert_eq!(get_expiration(&rec).unwrap(), "2025-01-31T23:59:59");
+    }
+
+    #[test]
+    fn expirations_absent_without_parseable_dates() {
+        let mut rec = record("", "", &[]);
+        let mut unparseable = indicator("", "", None, 1);
+        unparseable.expiration = Some("2024-02-30T00:00:00".to_string());
+        rec.indicators = vec![unparseable];
+
+        assert_eq!(get_earliest_expiration(&rec), None);
+        assert_eq!(get_expiration(&rec), None);
+    }
+
+    #[test]
+    fn invalid_expirations_name_the_indicator_and_error() {
+        let mut rec = record("", "", &[]);
+        rec.indicators = [(1, "2024-06-01T00:00:00"), (2, "2024-02-30T00:00:00"), (3, "not a date")]
+            .into_iter()
+            .map(|(id, expiration)| {
+                let mut ind = indicator("", "", None, 1);
+                ind.id = id;
+                ind.expiration = Some(expiration.to_string());
+                ind
+            })
+            .collect();
+        rec.indicators.push(indicator("", "", None, 1));
+
+        assert_eq!(
+            invalid_expirations(&rec),
+            vec![(2, DateParseError::InvalidDay(30)), (3, DateParseError::TooFewFields)]
+        );
+        assert_eq!(get_expiration(&rec).unwrap(), "2024-06-01T00:00:00");
+    }
+
+    #[test]
+    fn single_weak_keyword_stays_below_threshold() {
+        let rec = record("", "Visitors of the web portal were redirected", &[]);
+        let targets = labels(&classify_targets(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(targets, vec![Target::Unknown]);
+    }
+
+    #[test]
+    fn corroborating_weak_keywords_clear_threshold() {
+        let rec = record("", "The web portal and the company website were defaced", &[]);
+        let targets = labels(&classify_targets(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(targets, vec![Target::WebApp]);
+    }
+
+    #[test]
+    fn repeated_weak_keyword_does_not_corroborate_itself() {
+        let rec = record("Web portal outage", "Web portal visitors were redirected", &[]);
+        let targets = labels(&classify_targets(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(targets, vec![Target::Unknown]);
+    }
+
+    #[test]
+    fn threshold_can_be_overridden() {
+        let rec = record("", "Visitors of the web portal were redirected", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_targets(&text, 0.0)), vec![Target::WebApp]);
+
+        let rec = record("", "Ransomware deployed", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_attack_types(&text, 1.5)), vec![AttackType::Unknown]);
+    }
+
+    #[test]
+    fn config_reads_classification_threshold_param() {
+        let config = EnricherConfig::from_params(&params(&[])).unwrap();
+        assert_eq!(config.classification_threshold, DEFAULT_CLASSIFICATION_THRESHOLD);
+
+        let config = EnricherConfig::from_params(&params(&[("classification_threshold", "1.2")])).unwrap();
+        assert_eq!(config.classification_threshold, 1.2);
+
+        assert!(EnricherConfig::from_params(&params(&[("classification_threshold", "high")])).is_err());
+    }
+
+    #[test]
+    fn array_map_preserves_source_pulse() {
+        let mut first = record("LockBit wave", "ransomware", &[]);
+        first.id = "64f0a1".to_string();
+        first.author_name = "AlienVault".to_string();
+        let mut second = record("Emotet resurgence", "trojan", &[]);
+        second.id = "64f0b2".to_string();
+
+        let output = run_array_map(&pulse(vec![first, second]));
+        assert_eq!(output.len(), 2);
+
+        let enriched: Vec<EnrichedThreatRecord> = output
+            .iter()
+            .map(|(_, value)| serde_json::from_slice(value.as_ref()).unwrap())
+            .collect();
+        assert_eq!(enriched[0].source_id, "64f0a1");
+        assert_eq!(enriched[0].source_name, "LockBit wave");
+        assert_eq!(enriched[0].author, "AlienVault");
+        assert_eq!(enriched[1].source_id, "64f0b2");
+        assert_eq!(enriched[1].source_name, "Emotet resurgence");
+
+        let keys: Vec<&[u8]> = output.iter().map(|(key, _)| key.as_ref().unwrap().as_ref()).collect();
+        assert_eq!(keys, vec![b"64f0a1".as_slice(), b"64f0b2".as_slice()]);
+    }
+
+    #[test]
+    fn cve_reference_classifies_without_keywords() {
+        let rec = record("Gateway campaign", "Actor exploits CVE-2024-12345 on exposed appliances", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(classify_urgency(&text, config()).severity, Severity::Medium);
+
+        let evidence = collect_evidence(&text, THRESHOLD);
+        assert!(evidence.iter().any(|hit| {
+            hit.classification == Classification::Urgency(Urgency::Medium) && hit.keyword == r"cve \d{4} \d{4,7}"
+        }));
+    }
+
+    #[test]
+    fn mitre_technique_id_implies_medium_severity() {
+        let rec = record("Loader", "Uses T1059.001 for execution", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Medium);
+
+        let rec = record("Loader", "Build t10590 seen", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Unknown);
+    }
+
+    #[test]
+    fn keyword_severity_overrides_pattern_severity() {
+        let rec = record("Critical gateway flaw", "Exploitation of CVE-2024-12345", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Critical);
+    }
+
+    #[test]
+    fn bitcoin_address_implies_ransomware() {
+        let rec = record("Extortion note", "Pay to bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware]);
+
+        let rec = record("Extortion note", "Send to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+
+        let rec = record("Extortion note", "The locker asks for 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware]);
+    }
+
+    #[test]
+    fn legacy_bitcoin_pattern_uses_the_base58_alphabet() {
+        let hits = attack_type_matcher().hits("Send to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2");
+        assert_eq!(hits.iter().map(|hit| hit.weight).collect::<Vec<_>>(), vec![WEAK]);
+        // Base58 leaves out 0, O, I and l.
+        assert!(attack_type_matcher().hits("Send to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN0").is_empty());
+        assert!(attack_type_matcher().hits("Send to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVNO").is_empty());
+        assert!(attack_type_matcher().hits("Send to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVNI").is_empty());
+    }
+
+    #[test]
+    fn hex_hashes_are_not_bitcoin_addresses() {
+        let rec = record("Sample", "md5 1a79a4d60de6718e8e5b326e338ae533", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+    }
+
+    #[test]
+    fn array_map_keys_by_pulse_id() {
+        let mut keyed = record("LockBit wave", "ransomware", &[]);
+        keyed.id = "64f0a1".to_string();
+        let mut unkeyed = record("Anonymous pulse", "ransomware", &[]);
+        unkeyed.id = String::new();
+        let mut resent = unkeyed.clone();
+        resent.description = "ransomware and phishing".to_string();
+        let mut renamed = unkeyed.clone();
+        renamed.name = "Anonymous pulse II".to_string();
+        let mut edited = unkeyed.clone();
+        edited.modified = "2024-02-01T00:00:00".to_string();
+        assert_ne!(record_key(&edited).as_ref(), record_key(&unkeyed).as_ref());
+
+        let output = run_array_map(&pulse(vec![keyed, unkeyed, resent, renamed]));
+        assert_eq!(output.len(), 4);
+        assert_eq!(output[0].0.as_ref().unwrap().as_ref(), b"64f0a1");
+        let fallback = output[1].0.as_ref().unwrap().as_ref();
+        assert_eq!(fallback.len(), 16);
+        assert_eq!(output[2].0.as_ref().unwrap().as_ref(), fallback);
+        assert_ne!(output[3].0.as_ref().unwrap().as_ref(), fallback);
+
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[1].1.as_ref()).unwrap();
+        assert_eq!(enriched.source_id, "");
+    }
+
+    #[test]
+    fn negation_at_sentence_start_suppresses_hits() {
+        let rec = record("Loader update", "No ransomware component was observed", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+
+        let rec = record("Loader update", "Not a DDoS campaign", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+    }
+
+    #[test]
+    fn negation_does_not_cross_sentence_punctuation() {
+        let rec = record("Loader update", "Not a phishing lure. Ransomware was deployed", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware]);
+
+        let rec = record("Loader update", "Seen without ransomware, only a dropper", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Trojan]);
+    }
+
+    #[test]
+    fn negated_attack_type_leaves_the_other_one() {
+        let rec = record("Loader update", "This campaign is not ransomware but a wiper", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Wiper]);
+
+        let rec = record("Loader update", "Ransomware detected on two hosts", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware]);
+    }
+
+    #[test]
+    fn positive_mention_outweighs_negated_mention() {
+        let rec = record("Loader update", "This isn't ransomware at first, but later ransomware is dropped", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware]);
+    }
+
+    #[test]
+    fn negation_applies_to_every_classifier() {
+        let text_of = |description: &str| record("Campaign", description, &[]);
+
+        let rec = text_of("The lure did not use email");
+        let vectors = classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&vectors), vec![AttackVector::Unknown]);
+
+        let rec = text_of("No infrastructure was affected");
+        let targets = classify_targets(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&targets), vec![Target::Unknown]);
+
+        let rec = text_of("The issue is not critical");
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Unknown);
+
+        let rec = text_of("The issue is critical");
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Critical);
+    }
+
+    #[test]
+    fn separator_variants_match_one_keyword() {
+        for description in ["credential-stuffing wave", "credential stuffing wave", "credential_stuffing wave"] {
+            let rec = record("", description, &[]);
+            let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+            assert_eq!(labels(&types), vec![AttackType::BruteForce], "{description}");
+        }
+
+        let rec = record("", "Spear_Phishing lure", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Phishing]);
+    }
+
+    #[test]
+    fn multi_word_keywords_match_across_newlines() {
+        let rec = record("", "Blind SQL\n  injection against the login form", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::SQLInjection]);
+    }
+
+    #[test]
+    fn normalize_collapses_separators() {
+        assert_eq!(normalize("  Spear--Phishing_\tKit \n"), "spear phishing kit");
+        assert_eq!(normalize("CVE-2024-12345"), "cve 2024 12345");
+    }
+
+    #[test]
+    fn keyword_tables_are_normalized() {
+        let keywords: Vec<&str> = attack_type_keywords().keys()
+            .chain(attack_vector_keywords().keys())
+            .chain(urgency_keywords().keys())
+            .chain(target_keywords().keys())
+            .chain(sector_keywords().keys())
+            .copied()
+            .collect();
+        for keyword in keywords {
+            assert_eq!(normalize(keyword), keyword);
+        }
+    }
+
+    #[test]
+    fn attack_type_scores_reflect_hit_share() {
+        let rec = record("Ransomware campaign", "Ransomware delivered through phishing", &[]);
+        assert_eq!(
+            classify_attack_types_scored(&FlattenedText::new(&rec)),
+            vec![(AttackType::Ransomware, 0.67), (AttackType::Phishing, 0.33)]
+        );
+
+        let rec = record("Quarterly report", "", &[]);
+        assert_eq!(classify_attack_types_scored(&FlattenedText::new(&rec)), vec![(AttackType::Unknown, 0.0)]);
+
+        let output = run_array_map(&pulse(vec![record("Ransomware campaign", "Ransomware delivered through phishing", &[])]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.attack_type_scores, vec![(AttackType::Ransomware, 0.67), (AttackType::Phishing, 0.33)]);
+        assert_eq!(labels(&enriched.attack_types), vec![AttackType::Ransomware, AttackType::Phishing]);
+    }
+
+    #[test]
+    fn classifies_english_keywords_in_turkish_text() {
+        let rec = record("", "Türkiye'deki bankaları hedef alan PHİSHİNG kampanyası", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Phishing]);
+    }
+
+    #[test]
+    fn classifies_english_keywords_in_russian_text() {
+        let rec = record("", "фишинг campaign uses phishing kit «ransomware»", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Phishing]);
+    }
+
+    #[test]
+    fn classifies_english_keywords_in_chinese_text() {
+        let rec = record("", "该团伙使用勒索软件ransomware攻击，并通过phishing邮件传播。", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Phishing]);
+    }
+
+    #[test]
+    fn normalize_strips_invisible_characters() {
+        assert_eq!(normalize("ransom\u{200B}ware\u{0007} kit\u{FEFF}"), "ransomware kit");
+        assert_eq!(normalize("İNFOSTEALER"), "infostealer");
+
+        let rec = record("", "New ransom\u{200D}ware strain", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware]);
+    }
+
+    #[test]
+    fn oversized_descriptions_are_truncated_and_flagged() {
+        let mut description = "ransomware ".to_string();
+        description.push_str(&"a1b2c3 ".repeat(5 * 1024 * 1024 / 7));
+        description.push_str("phishing");
+        let rec = record("Dump", &description, &["botnet"]);
+
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert!(enriched.truncated);
+        assert_eq!(labels(&enriched.attack_types), vec![AttackType::Ransomware]);
+    }
+
+    #[test]
+    fn truncation_respects_char_boundaries() {
+        let rec = record("Фишинг", "phishing", &[]);
+        let text = FlattenedText::with_limit(&rec, 3);
+        assert!(text.truncated);
+        assert_eq!(text.fields, vec![(TextSource::Name, "ф".into())]);
+
+        let text = FlattenedText::with_limit(&rec, 20);
+        assert!(!text.truncated);
+        assert_eq!(text.fields.len(), 2);
+    }
+
+    #[test]
+    fn config_reads_max_text_bytes_param() {
+        assert_eq!(EnricherConfig::from_params(&params(&[])).unwrap().max_text_bytes, DEFAULT_MAX_TEXT_BYTES);
+        let config = EnricherConfig::from_params(&params(&[("max_text_bytes", "1024")])).unwrap();
+        assert_eq!(config.max_text_bytes, 1024);
+        assert!(EnricherConfig::from_params(&params(&[("max_text_bytes", "-1")])).is_err());
+    }
+
+    #[test]
+    fn overlapping_vector_keywords_count_once() {
+        let rec = record("", "SQL injection and SQLi probes alongside XSS payloads", &[]);
+        let text = FlattenedText::new(&rec);
+        let vectors = classify_attack_vectors(&text, THRESHOLD);
+        assert_eq!(labels(&vectors), vec![AttackVector::WebApplication]);
+
+        let matches = count_attack_vector_matches(&text, &vectors);
+        assert_eq!(matches, vec![(AttackVector::WebApplication, 3)]);
+        assert_eq!(dominant_vector(&matches), AttackVector::WebApplication);
+    }
+
+    #[test]
+    fn dominant_vector_prefers_most_matches_then_declaration_order() {
+        let rec = record("", "Phishing email with a malicious attachment targeting a web application", &[]);
+        let text = FlattenedText::new(&rec);
+        let matches = count_attack_vector_matches(&text, &classify_attack_vectors(&text, THRESHOLD));
+        assert_eq!(dominant_vector(&matches), AttackVector::Email);
+
+        let tied = [(AttackVector::Network, 2), (AttackVector::WebApplication, 2)];
+        assert_eq!(dominant_vector(&tied), AttackVector::WebApplication);
+        assert_eq!(dominant_vector(&[]), AttackVector::Unknown);
+    }
+
+    #[test]
+    fn cryptominers_classify_as_cryptojacking() {
+        let rec = record("", "Exposed Docker hosts drop an XMRig coinminer", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Cryptojacking]);
+
+        let rec = record("", "Worm spreads a Monero miner", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Malware, AttackType::Cryptojacking]);
+    }
+
+    #[test]
+    fn exact_tag_classifies_without_description() {
+        let rec = record("", "", &["Botnet"]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(types, vec![Scored { label: AttackType::Botnet, confidence: TAG_CONFIDENCE }]);
+    }
+
+    #[test]
+    fn tag_aliases_only_match_whole_tags() {
+        let rec = record("", "", &["Emotet", "IoT"]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_attack_types(&text, THRESHOLD)), vec![AttackType::Trojan]);
+        assert_eq!(labels(&classify_targets(&text, THRESHOLD)), vec![Target::IotDevices]);
+
+        let rec = record("", "Emotet resurfaces", &["emotet loader"]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+    }
+
+    #[test]
+    fn tag_labels_carry_full_confidence() {
+        let rec = record("", "Ransomware delivered by phishing", &["phishing"]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Phishing]);
+        assert_eq!(types[1].confidence, TAG_CONFIDENCE);
+        assert!(types[0].confidence < TAG_CONFIDENCE);
+    }
+
+    #[test]
+    fn weak_keyword_tags_are_not_exact_matches() {
+        let rec = record("", "", &["server"]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_targets(&text, THRESHOLD)), vec![Target::Unknown]);
+        assert!(collect_evidence(&text, THRESHOLD).iter().all(|hit| !hit.exact_tag));
+    }
+
+    #[test]
+    fn evidence_marks_exact_tag_hits() {
+        let rec = record("", "Phishing lure", &["Mirai", "new phishing wave"]);
+        let evidence = collect_evidence(&FlattenedText::new(&rec), THRESHOLD);
+        assert!(evidence.contains(&Evidence {
+            classification: Classification::AttackType(AttackType::Botnet),
+            keyword: "mirai".to_string(),
+            source: TextSource::Tag(0),
+            exact_tag: true,
+        }));
+        assert!(evidence.contains(&Evidence {
+            classification: Classification::AttackType(AttackType::Phishing),
+            keyword: "phishing".to_string(),
+            source: TextSource::Tag(1),
+            exact_tag: false,
+        }));
+    }
+
+    #[test]
+    fn apt_keywords_classify_as_apt() {
+        let rec = record("", "State-sponsored intrusion set", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::APT]);
+
+        let rec = record("APT28 targets ministries", "", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::APT]);
+    }
+
+    #[test]
+    fn named_adversary_with_techniques_implies_apt() {
+        let mut rec = record("Diplomatic entities targeted", "", &[]);
+        rec.adversary = "APT29".to_string();
+        rec.attack_ids = vec!["T1082".to_string()];
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::APT]);
+
+        rec.attack_ids.clear();
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+    }
+
+    #[test]
+    fn placeholder_adversary_is_not_attribution() {
+        let mut rec = record("Exploitation of CVE-2024-1234", "", &[]);
+        rec.adversary = "Unknown".to_string();
+        rec.attack_ids = vec!["T1082".to_string()];
+        rec.targeted_countries = vec!["US".to_string(), "DE".to_string(), "FR".to_string()];
+        rec.indicators = (0..25).map(|_| indicator("", "", None, 1)).collect();
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert!(!labels(&types).contains(&AttackType::APT));
+        // As in threat_score_adds_adversary_countries_and_cves, less the
+        // adversary's 10.
+        assert_eq!(threat_score_of(rec), 56);
+    }
+
+    #[test]
+    fn attribution_adds_apt_alongside_keywords() {
+        let mut rec = record("", "Spearphishing drops a backdoor", &[]);
+        rec.adversary = "Cozy Bear".to_string();
+        rec.attack_ids = vec!["T1566.001".to_string()];
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Phishing, AttackType::Trojan, AttackType::APT]);
+    }
+
+    fn typed_indicator(type_: &str) -> OTXIndicator {
+        let mut ind = indicator("", "", None, 1);
+        ind.type_ = type_.to_string();
+        ind
+    }
+
+    #[test]
+    fn url_only_pulse_infers_web_vector() {
+        let mut rec = record("", "", &[]);
+        rec.indicators = vec![typed_indicator("URL"), typed_indicator("URL"), typed_indicator("FileHash-SHA256")];
+        let vectors = classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(vectors, vec![Scored { label: AttackVector::WebApplication, confidence: 0.33 }]);
+    }
+
+    #[test]
+    fn mixed_indicator_types_infer_each_vector() {
+        let mut rec = record("", "", &[]);
+        rec.indicators = vec![
+            typed_indicator("domain"),
+            typed_indicator("email"),
+            typed_indicator("IPv6"),
+            typed_indicator("CIDR"),
+            typed_indicator("hostname"),
+        ];
+        let vectors = classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&vectors), vec![AttackVector::Email, AttackVector::WebApplication, AttackVector::Network]);
+    }
+
+    #[test]
+    fn keyword_vectors_take_precedence_over_indicator_types() {
+        let mut rec = record("", "Phishing email campaign", &[]);
+        rec.indicators = vec![typed_indicator("email"), typed_indicator("IPv4")];
+        let text = FlattenedText::new(&rec);
+        let vectors = classify_attack_vectors(&text, THRESHOLD);
+        assert_eq!(labels(&vectors), vec![AttackVector::Email, AttackVector::Network]);
+
+        let keyword_only = classify_attack_vectors(&FlattenedText::new(&record("", "Phishing email campaign", &[])), THRESHOLD);
+        assert_eq!(vectors[0], keyword_only[0]);
+    }
+
+    #[test]
+    fn recent_cve_with_exploit_kit_is_zero_day() {
+        let rec = record("", "CVE-2024-1234 exploit kit", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::ZeroDay]);
+    }
+
+    #[test]
+    fn old_cve_is_exploit() {
+        let rec = record("", "Campaign abuses CVE-2017-11882 in Office", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Exploit]);
+
+        let rec = record("", "CVE-2021-44228 exploit kit", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Exploit]);
+    }
+
+    #[test]
+    fn zero_day_keywords_need_no_cve() {
+        let rec = record("", "Actors chained a zero-day in the VPN appliance", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::ZeroDay]);
+
+        let rec = record("", "Unpatched CVE-2023-9999 reported", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::ZeroDay]);
+    }
+
+    #[test]
+    fn malware_family_aliases_resolve() {
+        assert_eq!(malware_family_type("LockBit 3.0"), Some(AttackType::Ransomware));
+        assert_eq!(malware_family_type("lockbit3"), Some(AttackType::Ransomware));
+        assert_eq!(malware_family_type("LockBit-v2"), Some(AttackType::Ransomware));
+        assert_eq!(malware_family_type("AgentTesla"), Some(AttackType::Spyware));
+        assert_eq!(malware_family_type("Agent Tesla"), Some(AttackType::Spyware));
+        assert_eq!(malware_family_type("Mozi"), Some(AttackType::Botnet));
+        assert_eq!(malware_family_type("Unheard Of"), None);
+        assert_eq!(malware_family_type("3.0"), None);
+    }
+
+    #[test]
+    fn malware_families_classify_before_keywords() {
+        let mut rec = record("", "Phishing lure", &[]);
+        rec.malware_families = vec!["LockBit 3.0".to_string(), "Conti".to_string()];
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Phishing]);
+        assert_eq!(types[0].confidence, TAG_CONFIDENCE);
+    }
+
+    #[test]
+    fn unknown_malware_families_pass_through() {
+        let mut rec = record("", "", &[]);
+        rec.malware_families = vec!["Mirai".to_string(), "NewLoader".to_string()];
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.malware_families, vec!["mirai".to_string(), "newloader".to_string()]);
+        assert_eq!(labels(&enriched.attack_types), vec![AttackType::Botnet]);
+    }
+
+    #[test]
+    fn extract_cves_dedupes_in_order_of_appearance() {
+        let mut rec = record("Exploitation of CVE-2024-3400", "Follow-on use of cve-2023-4966 and CVE-2024-3400", &[]);
+        rec.indicators = vec![indicator("CVE-2023-4966 scanner", "", None, 1)];
+        rec.tags = vec!["CVE-2024-21887".to_string()];
+        assert_eq!(extract_cves(&rec), vec!["CVE-2024-3400", "CVE-2023-4966", "CVE-2024-21887"]);
+    }
+
+    #[test]
+    fn extract_cves_requires_full_identifiers() {
+        let rec = record("", "See CVE-2024-12 and XCVE-2024-1234 and CVE-2024-123456", &[]);
+        assert_eq!(extract_cves(&rec), vec!["CVE-2024-123456"]);
+    }
+
+    fn with_attack_ids(ids: &[&str]) -> OTXRecord {
+        let mut rec = record("", "", &[]);
+        rec.attack_ids = ids.iter().map(|id| id.to_string()).collect();
+        rec
+    }
+
+    #[test]
+    fn mitre_techniques_resolve_names_and_tactics() {
+        let rec = with_attack_ids(&["T1566", "T1059.001", "T1059.999", "T9999"]);
+        let techniques = mitre_techniques(&rec);
+        let summary: Vec<(&str, Option<&str>, Option<&str>)> = techniques
+            .iter()
+            .map(|t| (t.id.as_str(), t.name.as_deref(), t.tactic.as_deref()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("T1566", Some("Phishing"), Some("Initial Access")),
+            ("T1059.001", Some("PowerShell"), Some("Execution")),
+            ("T1059.999", Some("Command and Scripting Interpreter"), Some("Execution")),
+            ("T9999", None, None),
+        ]);
+    }
+
+    #[test]
+    fn malformed_attack_ids_are_parsed_loosely() {
+        let rec = with_attack_ids(&["t1566 - phishing", "T1566", "phishing", "", "T1486"]);
+        assert_eq!(technique_ids(&rec), vec!["T1566", "T1486"]);
+    }
+
+    #[test]
+    fn techniques_feed_attack_type_classification() {
+        let rec = with_attack_ids(&["T1566.002", "T1486", "T1498", "T1082"]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Ddos, AttackType::Phishing]);
+    }
+
+    #[test]
+    fn threat_actor_aliases_share_a_canonical_name() {
+        assert_eq!(known_threat_actor("Fancy Bear"), Some("APT28"));
+        assert_eq!(known_threat_actor("APT 28"), Some("APT28"));
+        assert_eq!(known_threat_actor("apt-28"), Some("APT28"));
+        assert_eq!(known_threat_actor("Sofacy"), Some("APT28"));
+        assert_eq!(known_threat_actor("Some Crew"), None);
+    }
+
+    #[test]
+    fn threat_actor_falls_back_to_raw_adversary() {
+        let mut rec = record("", "", &[]);
+        assert_eq!(threat_actor(&rec), None);
+
+        rec.adversary = "  ".to_string();
+        assert_eq!(threat_actor(&rec), None);
+
+        rec.adversary = "Cozy Bear".to_string();
+        assert_eq!(threat_actor(&rec), Some("APT29".to_string()));
+
+        rec.adversary = "Some Crew".to_string();
+        assert_eq!(threat_actor(&rec), Some("Some Crew".to_string()));
+    }
+
+    #[test]
+    fn adversary_placeholders_are_none() {
+        let mut rec = record("", "", &[]);
+        for placeholder in ["", "  ", "Unknown", " N/A ", "none", "-"] {
+            rec.adversary = placeholder.to_string();
+            assert_eq!(adversary(&rec), None, "{placeholder:?}");
+            assert_eq!(threat_actor(&rec), None, "{placeholder:?}");
+        }
+
+        rec.adversary = " Cozy Bear ".to_string();
+        rec.author_name = "AlienVault".to_string();
+        let enriched = enrich_record(&rec);
+        assert_eq!(enriched.adversary.as_deref(), Some("Cozy Bear"));
+        assert_eq!(enriched.threat_actor.as_deref(), Some("APT29"));
+        assert_eq!(enriched.author, "AlienVault");
+        let json = serde_json::to_value(&enriched).unwrap();
+        assert_eq!(json["adversary"], "Cozy Bear");
+        assert_eq!(json["author"], "AlienVault");
+    }
+
+    #[test]
+    fn known_apt_group_bumps_severity() {
+        let mut rec = record("", "", &[]);
+        rec.adversary = "Fancy Bear".to_string();
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Low);
+
+        rec.indicators = vec![indicator("", "", None, 1)];
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Medium);
+
+        rec.description = "Moderate activity".to_string();
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Critical);
+
+        rec.adversary = "Some Crew".to_string();
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Medium);
+    }
+
+    fn ioc_indicator(type_: &str, value: &str, is_active: u8) -> OTXIndicator {
+        let mut ind = indicator("", "", None, is_active);
+        ind.type_ = type_.to_string();
+        ind.indicator = value.to_string();
+        ind
+    }
+
+    #[test]
+    fn extract_iocs_types_active_indicators() {
+        let mut rec = record("", "", &[]);
+        rec.indicators = vec![
+            ioc_indicator("IPv4", "203.0.113.7", 1),
+            ioc_indicator("domain", "evil.example", 1),
+            ioc_indicator("hostname", "cdn.evil.example", 1),
+            ioc_indicator("FileHash-SHA256", "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08", 1),
+            ioc_indicator("IPv4", "198.51.100.1", 0),
+            ioc_indicator("YARA", "rule_x", 1),
+        ];
+        let iocs = extract_iocs(&rec);
+        let iocs: Vec<(&str, IocType)> = iocs.iter().map(|ioc| (ioc.value.as_str(), ioc.ioc_type)).collect();
+        assert_eq!(iocs, vec![
+            ("203.0.113.7", IocType::IPv4),
+            ("evil.example", IocType::Domain),
+            ("cdn.evil.example", IocType::Domain),
+            ("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08", IocType::FileHashSha256),
+            ("rule_x", IocType::Other),
+        ]);
+    }
+
+    #[test]
+    fn ioc_types_map_from_otx_types() {
+        assert_eq!(IocType::from_otx_type("IPv6"), IocType::IPv6);
+        assert_eq!(IocType::from_otx_type("URL"), IocType::Url);
+        assert_eq!(IocType::from_otx_type("FileHash-MD5"), IocType::FileHashMd5);
+        assert_eq!(IocType::from_otx_type("FileHash-SHA1"), IocType::FileHashSha1);
+        assert_eq!(IocType::from_otx_type("email"), IocType::Email);
+        assert_eq!(IocType::from_otx_type("CVE"), IocType::Other);
+    }
+
+    #[test]
+    fn extract_cves_reads_references_and_spaced_ids() {
+        let mut rec = record("", "Exploits CVE 2023-4966 in NetScaler", &[]);
+        rec.references = vec![
+            "https://nvd.nist.gov/vuln/detail/CVE-2023-4966".to_string(),
+            "https://example.com/advisory?id=cve-2024-21887&lang=en".to_string(),
+        ];
+        assert_eq!(extract_cves(&rec), vec!["CVE-2023-4966", "CVE-2024-21887"]);
+
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.cve_ids, vec!["CVE-2023-4966", "CVE-2024-21887"]);
+    }
+
+    #[test]
+    fn cve_references_raise_severity_to_medium() {
+        let mut rec = record("", "Low impact advisory", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Low);
+
+        rec.references = vec!["https://nvd.nist.gov/vuln/detail/CVE-2023-4966".to_string()];
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Medium);
+
+        rec.description = "Critical advisory".to_string();
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Critical);
+    }
+
+    #[test]
+    fn indicators_split_into_active_and_expired() {
+        let mut past = indicator("", "", None, 1);
+        past.expiration = Some("2001-01-01T00:00:00".to_string());
+        let mut future = indicator("", "", None, 1);
+        future.expiration = Some("2999-01-01T00:00:00".to_string());
+        let open_ended = indicator("", "", None, 1);
+        let inactive = indicator("", "", None, 0);
+        let mut unparseable = indicator("", "", None, 1);
+        unparseable.expiration = Some("soon".to_string());
+
+        let mut rec = record("", "", &[]);
+        rec.indicators = vec![past, future, open_ended, inactive, unparseable];
+        assert_eq!(count_indicators(&rec, SystemTime::now()), (3, 2));
+
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!((enriched.active_indicator_count, enriched.expired_indicator_count), (3, 2));
+    }
+
+    fn urgency_score(record: &OTXRecord) -> u8 {
+        classify_urgency_score(record, classify_urgency(&FlattenedText::new(record), config()))
+    }
+
+    #[test]
+    fn critical_active_records_score_high() {
+        let mut rec = record("Critical campaign", "", &[]);
+        rec.indicators = (0..25).map(|_| indicator("", "", None, 1)).collect();
+        assert_eq!(urgency_score(&rec), 100);
+    }
+
+    #[test]
+    fn inactive_low_records_score_low() {
+        let mut rec = record("Low priority", "", &[]);
+        rec.indicators = vec![indicator("", "", None, 0), indicator("", "", None, 0)];
+        assert_eq!(urgency_score(&rec), 12);
+
+        assert_eq!(urgency_score(&record("", "", &[])), 0);
+    }
+
+    #[test]
+    fn urgency_score_scales_with_active_share() {
+        let mut rec = record("Moderate", "", &[]);
+        rec.indicators = vec![
+            indicator("", "", None, 1),
+            indicator("", "", None, 0),
+            indicator("", "", None, 1),
+            indicator("", "", None, 0),
+        ];
+        assert_eq!(urgency_score(&rec), 30 + 15 + 4);
+    }
+
+    #[test]
+    fn cvss_bare_scores_are_extracted() {
+        let rec = record("Gateway flaw (CVSS 9.8)", "Rated CVSSv3 score: 7.5 by the vendor", &[]);
+        assert_eq!(extract_cvss_score(&rec), Some(9.8));
+
+        let rec = record("", "cvss=5.3", &[]);
+        assert_eq!(extract_cvss_score(&rec), Some(5.3));
+    }
+
+    #[test]
+    fn cvss_vector_strings_are_scored() {
+        assert_eq!(cvss_vector_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"), Some(9.8));
+        assert_eq!(cvss_vector_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N"), Some(6.1));
+        assert_eq!(cvss_vector_score("CVSS:3.0/AV:L/AC:L/PR:L/UI:N/S:U/C:H/I:H/A:H"), Some(7.8));
+        assert_eq!(cvss_vector_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N"), Some(0.0));
+        assert_eq!(cvss_vector_score("CVSS:3.1/AV:N/AC:L"), None);
+
+        let mut rec = record("", "", &[]);
+        rec.references = vec!["https://example.com/advisory CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N".to_string()];
+        assert_eq!(extract_cvss_score(&rec), Some(6.1));
+    }
+
+    #[test]
+    fn cvss_garbage_is_ignored() {
+        let rec = record("", "CVSS 99 and CVSS 10.5 are not scores", &[]);
+        assert_eq!(extract_cvss_score(&rec), None);
+        assert_eq!(extract_cvss_score(&record("", "No scores here", &[])), None);
+    }
+
+    #[test]
+    fn cvss_sets_severity_without_keywords() {
+        let rec = record("", "Rated CVSS 9.8", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Critical);
+
+        let rec = record("", "Rated CVSS 5.0", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Medium);
+
+        let rec = record("", "Minor issue rated CVSS 9.8", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Low);
+    }
+
+    #[test]
+    fn highest_severity_keyword_wins() {
+        for description in ["Low priority, but critical for finance", "Critical, though low priority"] {
+            let rec = record("", description, &[]);
+            assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Critical, "{description}");
+        }
+
+        let rec = record("Hot topic", "Moderate and low impact", &[]);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, Severity::Medium);
+    }
+
+    #[test]
+    fn industries_map_to_sectors_in_singular_and_plural() {
+        assert_eq!(sector_for_industry("Hospital"), Some(Sector::Healthcare));
+        assert_eq!(sector_for_industry("Hospitals"), Some(Sector::Healthcare));
+        assert_eq!(sector_for_industry("Universities"), Some(Sector::Education));
+        assert_eq!(sector_for_industry("Governments"), Some(Sector::Government));
+        assert_eq!(sector_for_industry("Banks"), Some(Sector::Finance));
+    }
+
+    #[test]
+    fn financial_services_and_finance_are_one_sector() {
+        assert_eq!(sector_for_industry("Financial Services"), Some(Sector::Finance));
+        assert_eq!(sector_for_industry("financial-services"), Some(Sector::Finance));
+        assert_eq!(sector_for_industry("Finance"), Some(Sector::Finance));
+
+        let mut rec = record("Campaign", "", &[]);
+        rec.industries = vec!["Finance".to_string(), "Financial Services".to_string()];
+        let (sectors, raw_industries) = classify_sectors(&FlattenedText::new(&rec));
+        assert_eq!(sectors, vec![Sector::Finance]);
+        assert!(raw_industries.is_empty());
+    }
+
+    #[test]
+    fn description_keywords_add_sectors() {
+        let mut rec = record("Ransomware campaign", "The group targets hospitals and universities", &[]);
+        rec.industries = vec!["Telecommunications".to_string()];
+        let (sectors, _) = classify_sectors(&FlattenedText::new(&rec));
+        assert_eq!(sectors, vec![Sector::Healthcare, Sector::Education, Sector::Telecom]);
+    }
+
+    #[test]
+    fn unknown_industries_are_other_and_kept_raw() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.industries = vec!["Aerospace".to_string(), "Legal".to_string(), "Energy".to_string()];
+        let (sectors, raw_industries) = classify_sectors(&FlattenedText::new(&rec));
+        assert_eq!(sectors, vec![Sector::Energy, Sector::Other]);
+        assert_eq!(raw_industries, vec!["Aerospace".to_string(), "Legal".to_string()]);
+
+        let rec = record("Campaign", "No sector named here", &[]);
+        assert_eq!(classify_sectors(&FlattenedText::new(&rec)), (vec![], vec![]));
+    }
+
+    #[test]
+    fn tlp_parses_every_tlp_2_label() {
+        assert_eq!(Tlp::parse("TLP:CLEAR"), Tlp::Clear);
+        assert_eq!(Tlp::parse("TLP:GREEN"), Tlp::Green);
+        assert_eq!(Tlp::parse("TLP:AMBER"), Tlp::Amber);
+        assert_eq!(Tlp::parse("TLP:AMBER+STRICT"), Tlp::AmberStrict);
+        assert_eq!(Tlp::parse("TLP:RED"), Tlp::Red);
+
+        assert_eq!(Tlp::parse("white"), Tlp::Clear);
+        assert_eq!(Tlp::parse("Amber"), Tlp::Amber);
+        assert_eq!(Tlp::parse(" tlp:red "), Tlp::Red);
+        assert_eq!(Tlp::parse("purple"), Tlp::Unknown);
+        assert_eq!(Tlp::parse(""), Tlp::Unknown);
+    }
+
+    #[test]
+    fn unparseable_tlp_deserializes_as_unknown() {
+        let tlp: Tlp = serde_json::from_str("\"TLP:AMBER+STRICT\"").unwrap();
+        assert_eq!(tlp, Tlp::AmberStrict);
+        let tlp: Tlp = serde_json::from_str("\"secret\"").unwrap();
+        assert_eq!(tlp, Tlp::Unknown);
+
+        let mut rec = record("Campaign", "", &[]);
+        rec.tlp = Tlp::Red;
+        let round_trip: OTXRecord = serde_json::from_slice(&serde_json::to_vec(&rec).unwrap()).unwrap();
+        assert_eq!(round_trip.tlp, Tlp::Red);
+    }
+
+    #[test]
+    fn tlp_escalates_severity() {
+        let severity = |tlp: Tlp, description: &str| {
+            let mut rec = record("Campaign", description, &[]);
+            rec.tlp = tlp;
+            classify_urgency(&FlattenedText::new(&rec), config()).severity
+        };
+        assert_eq!(severity(Tlp::Clear, ""), Severity::Unknown);
+        assert_eq!(severity(Tlp::Green, ""), Severity::Unknown);
+        assert_eq!(severity(Tlp::Unknown, ""), Severity::Unknown);
+        assert_eq!(severity(Tlp::Amber, ""), Severity::Low);
+        assert_eq!(severity(Tlp::AmberStrict, ""), Severity::Low);
+        assert_eq!(severity(Tlp::Amber, "A low risk threat"), Severity::Medium);
+        assert_eq!(severity(Tlp::Amber, "A moderate threat"), Severity::Critical);
+        assert_eq!(severity(Tlp::Red, ""), Severity::Critical);
+    }
+
+    #[test]
+    fn escalate_moves_one_rank() {
+        assert_eq!(Severity::Unknown.escalate(), Severity::Low);
+        assert_eq!(Severity::Low.escalate(), Severity::Medium);
+        assert_eq!(Severity::Medium.escalate(), Severity::Critical);
+        assert_eq!(Severity::Critical.escalate(), Severity::Critical);
+    }
+
+    #[test]
+    fn tlp_is_emitted() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.tlp = Tlp::Amber;
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.tlp, Tlp::Amber);
+        assert!(!enriched.restricted);
+    }
+
+    #[test]
+    fn tlp_serializes_as_lowercase_label() {
+        let rec: OTXRecord = serde_json::from_value(serde_json::json!({ "id": "1", "name": "", "tlp": "TLP:AMBER+STRICT" })).unwrap();
+        let json = serde_json::to_value(enrich_record(&rec)).unwrap();
+        assert_eq!(json["tlp"], "amber+strict");
+        for (input, label) in [("TLP:AMBER", "amber"), ("White", "clear"), ("RED", "red"), ("secret", "unknown")] {
+            assert_eq!(String::from(Tlp::parse(input)), label, "{input}");
+        }
+    }
+
+    #[test]
+    fn redact_tlp_strips_free_text_from_amber_and_red_pulses() {
+        let config = EnricherConfig::from_params(&params(&[
+            ("redact_tlp", "true"),
+            ("evidence", "true"),
+            ("include_provenance", "true"),
+        ]))
+        .unwrap();
+        let mut rec = record("LockBit wave", "Ransomware encrypts file servers", &[]);
+        rec.references = vec!["https://example.com/report".to_string()];
+
+        for tlp in [Tlp::Amber, Tlp::AmberStrict, Tlp::Red] {
+            rec.tlp = tlp;
+            let enriched = enrich_record_with(&rec, &config);
+            assert!(enriched.references.is_empty(), "{tlp:?}");
+            assert_eq!(enriched.evidence, None, "{tlp:?}");
+            assert!(enriched.hits.is_empty(), "{tlp:?}");
+            assert_eq!(enriched.dropped_references, None, "{tlp:?}");
+            assert_eq!(labels(&enriched.attack_types), [AttackType::Ransomware], "{tlp:?}");
+        }
+
+        rec.tlp = Tlp::Green;
+        let enriched = enrich_record_with(&rec, &config);
+        assert_eq!(enriched.references, ["https://example.com/report"]);
+        assert!(enriched.evidence.is_some());
+        assert!(!enriched.hits.is_empty());
+
+        rec.tlp = Tlp::Red;
+        let enriched = enrich_record_with(&rec, &EnricherConfig::default());
+        assert_eq!(enriched.references, ["https://example.com/report"]);
+    }
+
+    #[test]
+    fn parse_tlp_ignores_case() {
+        assert_eq!(parse_tlp("AMBER"), Tlp::Amber);
+        assert_eq!(parse_tlp("red"), Tlp::Red);
+        assert_eq!(parse_tlp(""), Tlp::Unknown);
+    }
+
+    #[test]
+    fn red_pulses_are_restricted() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.tlp = parse_tlp("red");
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.tlp, Tlp::Red);
+        assert!(enriched.restricted);
+    }
+
+    fn dated_indicator(created: &str, is_active: u8) -> OTXIndicator {
+        let mut ind = indicator("", "", None, is_active);
+        ind.created = created.to_string();
+        ind
+    }
+
+    fn hot_at(rec: &OTXRecord, now: &str) -> bool {
+        is_hot(rec, parse_iso8601(now).unwrap(), &EnricherConfig::default()) == Some(true)
+    }
+
+    #[test]
+    fn old_but_active_indicators_are_cold() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = vec![
+            dated_indicator("2024-01-01T00:00:00", 1),
+            dated_indicator("2024-02-01T00:00:00", 1),
+            dated_indicator("2024-02-01T00:00:00", 1),
+        ];
+        assert!(!hot_at(&rec, "2026-01-01T00:00:00"));
+        assert!(hot_at(&rec, "2024-02-15T00:00:00"));
+    }
+
+    #[test]
+    fn new_but_inactive_indicators_are_cold() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = vec![
+            dated_indicator("2025-12-20T00:00:00", 0),
+            dated_indicator("2025-12-28T00:00:00", 0),
+            dated_indicator("2025-12-28T00:00:00", 0),
+        ];
+        assert!(!hot_at(&rec, "2026-01-01T00:00:00"));
+    }
+
+    #[test]
+    fn mixed_pulse_needs_a_share_of_recent_indicators() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = vec![
+            dated_indicator("2025-12-20T00:00:00", 1),
+            dated_indicator("2023-01-01T00:00:00", 1),
+            dated_indicator("2023-01-01T00:00:00", 1),
+            dated_indicator("2023-01-01T00:00:00", 1),
+        ];
+        assert!(hot_at(&rec, "2026-01-01T00:00:00"));
+
+        rec.indicators.push(dated_indicator("2023-01-01T00:00:00", 1));
+        assert!(!hot_at(&rec, "2026-01-01T00:00:00"));
+
+        // A longer window takes in the older indicators.
+        let config = EnricherConfig { hot_window_days: 4 * 365, ..EnricherConfig::default() };
+        assert_eq!(is_hot(&rec, parse_iso8601("2026-01-01T00:00:00").unwrap(), &config), Some(true));
+    }
+
+    #[test]
+    fn pulse_modified_date_stands_in_for_undated_indicators() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = vec![dated_indicator("", 1), dated_indicator("not a date", 1), dated_indicator("", 1)];
+        rec.modified = "2025-12-30T12:00:00".to_string();
+        assert!(hot_at(&rec, "2026-01-01T00:00:00"));
+        rec.modified = "2025-06-01T00:00:00".to_string();
+        assert!(!hot_at(&rec, "2026-01-01T00:00:00"));
+    }
+
+    #[test]
+    fn config_reads_hot_window_days_param() {
+        let config = EnricherConfig::from_params(&params(&[("hot_window_days", "7")])).unwrap();
+        assert_eq!(config.hot_window_days, 7);
+        assert_eq!(EnricherConfig::default().hot_window_days, DEFAULT_HOT_WINDOW_DAYS);
+        assert!(EnricherConfig::from_params(&params(&[("hot_window_days", "week")])).is_err());
+        assert!(EnricherConfig::from_params(&params(&[("hot_window_days", &u64::MAX.to_string())])).is_err());
+    }
+
+    #[test]
+    fn invalid_json_becomes_one_error_record() {
+        let value = b"{\"results\": [not json".to_vec();
+        let output = array_map(&SmartModuleRecord::new(fluvio_smartmodule::Record::new(value.clone()), 0, 0)).unwrap();
+        assert_eq!(output.len(), 1);
+        assert!(output[0].0.is_none());
+        let marker: EnrichmentError = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(marker.raw_len, value.len());
+        assert!(!marker.error.is_empty());
+    }
+
+    #[test]
+    fn malformed_result_does_not_drop_valid_ones() {
+        let mut value = serde_json::to_value(pulse(vec![record("Ransomware wave", "", &[])])).unwrap();
+        value["results"].as_array_mut().unwrap().push(serde_json::json!({ "id": 7 }));
+        let output = array_map(&SmartModuleRecord::new(
+            fluvio_smartmodule::Record::new(serde_json::to_vec(&value).unwrap()),
+            0,
+            0,
+        ))
+        .unwrap();
+        assert_eq!(output.len(), 2);
+
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.source_name, "Ransomware wave");
+        let marker: EnrichmentError = serde_json::from_slice(output[1].1.as_ref()).unwrap();
+        assert_eq!(marker.raw_len, r#"{"id":7}"#.len());
+        assert!(output[1].0.is_none());
+    }
+
+    #[test]
+    fn hot_needs_active_share_above_ratio() {
+        let now = "2026-01-01T00:00:00";
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = vec![
+            dated_indicator("2025-12-30T00:00:00", 1),
+            dated_indicator("2025-12-30T00:00:00", 1),
+            dated_indicator("2025-12-30T00:00:00", 0),
+            dated_indicator("2025-12-30T00:00:00", 0),
+        ];
+        // Exactly half active doesn't exceed the default ratio.
+        assert!(!hot_at(&rec, now));
+        rec.indicators[2].is_active = 1;
+        assert!(hot_at(&rec, now));
+
+        let strict = EnricherConfig { hot_active_ratio: 0.8, ..EnricherConfig::default() };
+        assert_eq!(is_hot(&rec, parse_iso8601(now).unwrap(), &strict), Some(false));
+    }
+
+    #[test]
+    fn proportion_outweighs_absolute_count() {
+        let now = "2026-01-01T00:00:00";
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = (0..10).map(|i| dated_indicator("2025-12-30T00:00:00", (i < 4) as u8)).collect();
+        assert!(!hot_at(&rec, now));
+        rec.indicators = (0..10).map(|i| dated_indicator("2025-12-30T00:00:00", (i < 6) as u8)).collect();
+        assert!(hot_at(&rec, now));
+    }
+
+    #[test]
+    fn tiny_pulses_are_never_hot() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = vec![dated_indicator("2025-12-30T00:00:00", 1); MIN_HOT_INDICATORS - 1];
+        assert!(!hot_at(&rec, "2026-01-01T00:00:00"));
+    }
+
+    #[test]
+    fn pulse_without_indicators_has_no_activity_signal() {
+        let rec = record("Campaign", "", &[]);
+        assert_eq!(is_hot(&rec, SystemTime::now(), &EnricherConfig::default()), None);
+        assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).temperature, Temperature::Unknown);
+    }
+
+    #[test]
+    fn config_reads_hot_active_ratio_param() {
+        let config = EnricherConfig::from_params(&params(&[("hot_active_ratio", "0.75")])).unwrap();
+        assert_eq!(config.hot_active_ratio, 0.75);
+        assert!(EnricherConfig::from_params(&params(&[("hot_active_ratio", "most")])).is_err());
+    }
+
+    #[test]
+    fn urgency_serializes_with_named_fields() {
+        let urgency = UrgencyAssessment { temperature: Temperature::Hot, severity: Severity::Critical };
+        let json = serde_json::to_value(urgency).unwrap();
+        assert_eq!(json, serde_json::json!({ "temperature": "Hot", "severity": "Critical" }));
+        assert_eq!(serde_json::from_value::<UrgencyAssessment>(json).unwrap(), urgency);
+    }
+
+    #[test]
+    fn tuple_shaped_urgency_still_deserializes() {
+        let urgency: UrgencyAssessment = serde_json::from_str(r#"["Cold", "Medium"]"#).unwrap();
+        assert_eq!(urgency, UrgencyAssessment { temperature: Temperature::Cold, severity: Severity::Medium });
+
+        let urgency: UrgencyAssessment = serde_json::from_str(r#"["Hot", "Critical"]"#).unwrap();
+        assert_eq!(urgency, UrgencyAssessment { temperature: Temperature::Hot, severity: Severity::Critical });
+    }
+
+    #[test]
+    fn minimal_pulse_deserializes_with_defaults() {
+        let json = r#"{
+            "results": [{
+                "id": "pulse-1",
+                "name": "Minimal pulse",
+                "description": "Ransomware wave",
+                "indicators": []
+            }]
+        }"#;
+        let pulse: OTXPulse = serde_json::from_str(json).unwrap();
+        assert_eq!(pulse.count, 0);
+        assert!(pulse.next.is_none());
+
+        let rec = &pulse.results[0];
+        assert!(rec.industries.is_empty());
+        assert!(rec.extract_source.is_empty());
+        assert!(rec.references.is_empty());
+        assert_eq!(rec.revision, 0);
+        assert_eq!(rec.public, 0);
+        assert_eq!(rec.tlp, Tlp::Unknown);
+        assert_eq!(rec.author_name, "");
+        assert!(!rec.more_indicators);
+
+        let output = run_array_map(&pulse);
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.source_name, "Minimal pulse");
+    }
+
+    fn threat_score_of(rec: OTXRecord) -> u8 {
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        enriched.threat_score
+    }
+
+    #[test]
+    fn threat_score_is_pinned_for_fixtures() {
+        let scores: Vec<u8> = fixture_records().into_iter().map(threat_score_of).collect();
+        // botnet: Low 5 + 1 active; phishing: Low 5 + 1 active;
+        // roundup: Low 5; SQLi: Critical 35.
+        assert_eq!(scores, vec![6, 6, 5, 35]);
+    }
+
+    #[test]
+    fn threat_score_adds_adversary_countries_and_cves() {
+        let mut rec = record("Exploitation of CVE-2024-1234", "", &[]);
+        rec.adversary = "Unknown crew".to_string();
+        rec.targeted_countries = vec!["US".to_string(), "DE".to_string(), "FR".to_string()];
+        rec.indicators = (0..25).map(|_| indicator("", "", None, 1)).collect();
+        // Medium 20 (CVE floor) + 20 active + adversary 10 + 3 countries 6 + CVE 10.
+        assert_eq!(threat_score_of(rec), 66);
+    }
+
+    #[test]
+    fn threat_score_tops_out_at_100() {
+        let mut rec = record("Critical ransomware", "", &["CVE-2024-1234"]);
+        rec.adversary = "APT28".to_string();
+        rec.targeted_countries = (0..8).map(|i| format!("C{i}")).collect();
+        let mut enriched: EnrichedThreatRecord = serde_json::from_slice(
+            run_array_map(&pulse(vec![rec.clone()]))[0].1.as_ref(),
+        )
+        .unwrap();
+        enriched.urgency.temperature = Temperature::Hot;
+        enriched.active_indicator_count = 40;
+        assert_eq!(compute_threat_score(&rec, &enriched), 100);
+    }
+
+    fn run_filter(pulse: &OTXPulse) -> bool {
+        let value = serde_json::to_vec(pulse).unwrap();
+        filter(&SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0)).unwrap()
+    }
+
+    #[test]
+    fn filter_drops_all_inactive_and_empty_pulses() {
+        let mut rec = record("Stale pulse", "", &[]);
+        rec.indicators = vec![indicator("", "", None, 0), indicator("", "", None, 0)];
+        assert!(!run_filter(&pulse(vec![rec, record("No indicators", "", &[])])));
+        assert!(!run_filter(&pulse(vec![])));
+    }
+
+    #[test]
+    fn filter_keeps_pulse_with_one_active_indicator() {
+        let mut stale = record("Stale pulse", "", &[]);
+        stale.indicators = vec![indicator("", "", None, 0)];
+        let mut live = record("Live pulse", "", &[]);
+        live.indicators = vec![indicator("", "", None, 0), indicator("", "", None, 1)];
+        assert!(run_filter(&pulse(vec![stale, live])));
+    }
+
+    #[test]
+    fn filter_keeps_malformed_input_for_array_map() {
+        let record = SmartModuleRecord::new(fluvio_smartmodule::Record::new(b"not json".to_vec()), 0, 0);
+        assert!(filter(&record).unwrap());
+    }
+
+    #[test]
+    fn severity_ordering_table() {
+        let ordered = [Severity::Unknown, Severity::Low, Severity::Medium, Severity::Critical];
+        for (i, a) in ordered.iter().enumerate() {
+            for (j, b) in ordered.iter().enumerate() {
+                assert_eq!(a.cmp(b), i.cmp(&j), "{:?} vs {:?}", a, b);
+            }
+        }
+        let mut shuffled = vec![Severity::Medium, Severity::Critical, Severity::Unknown, Severity::Low];
+        shuffled.sort();
+        assert_eq!(shuffled, ordered);
+        assert_eq!(Severity::max_severity(Severity::Low, Severity::Critical), Severity::Critical);
+        assert_eq!(Severity::max_severity(Severity::Unknown, Severity::Low), Severity::Low);
+    }
+
+    #[test]
+    fn temperature_and_assessment_ordering() {
+        assert!(Temperature::Cold < Temperature::Hot);
+        assert_eq!([Temperature::Hot, Temperature::Cold].iter().max(), Some(&Temperature::Hot));
+
+        let assessment = |temperature, severity| UrgencyAssessment { temperature, severity };
+        let critical_cold = assessment(Temperature::Cold, Severity::Critical);
+        let medium_hot = assessment(Temperature::Hot, Severity::Medium);
+        let medium_cold = assessment(Temperature::Cold, Severity::Medium);
+        assert!(critical_cold > medium_hot);
+        assert!(medium_hot > medium_cold);
+        assert_eq!(UrgencyAssessment::max_urgency(medium_cold, critical_cold), critical_cold);
+        assert_eq!([medium_cold, critical_cold, medium_hot].into_iter().max(), Some(critical_cold));
+
+        let distinct: std::collections::HashSet<UrgencyAssessment> = [medium_hot, medium_hot, medium_cold].into_iter().collect();
+        assert_eq!(distinct.len(), 2);
+    }
+
+    #[test]
+    fn ordering_leaves_serialized_names_unchanged() {
+        for (severity, name) in [
+            (Severity::Critical, "Critical"),
+            (Severity::Medium, "Medium"),
+            (Severity::Low, "Low"),
+            (Severity::Unknown, "Unknown"),
+        ] {
+            assert_eq!(serde_json::to_value(severity).unwrap(), serde_json::json!(name));
+        }
+        assert_eq!(serde_json::to_value(Temperature::Hot).unwrap(), serde_json::json!("Hot"));
+        assert_eq!(serde_json::to_value(Temperature::Cold).unwrap(), serde_json::json!("Cold"));
+        assert_eq!(serde_json::to_value(Urgency::Critical).unwrap(), serde_json::json!("Critical"));
+    }
+
+    #[test]
+    fn hot_threshold_param_raises_the_tipper_bar() {
+        let now = parse_iso8601("2026-01-01T00:00:00").unwrap();
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = (0..5).map(|i| dated_indicator("2025-12-30T00:00:00", (i < 3) as u8)).collect();
+        assert_eq!(is_hot(&rec, now, &EnricherConfig::default()), Some(true));
+
+        let config = EnricherConfig::from_params(&params(&[("hot_threshold", "2")])).unwrap();
+        assert_eq!(config.hot_threshold, 2);
+        assert_eq!(is_hot(&rec, now, &config), Some(false));
+    }
+
+    #[test]
+    fn min_active_indicators_param_requires_enough_active() {
+        let now = parse_iso8601("2026-01-01T00:00:00").unwrap();
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = vec![dated_indicator("2025-12-30T00:00:00", 1); 3];
+        assert_eq!(is_hot(&rec, now, &EnricherConfig::default()), Some(true));
+
+        let config = EnricherConfig::from_params(&params(&[("min_active_indicators", "4")])).unwrap();
+        assert_eq!(is_hot(&rec, now, &config), Some(false));
+        assert!(EnricherConfig::from_params(&params(&[("min_active_indicators", "-1")])).is_err());
+        assert!(EnricherConfig::from_params(&params(&[("hot_threshold", "high")])).is_err());
+    }
+
+    #[test]
+    fn no_indicators_and_no_cues_is_unknown() {
+        let rec = record("Weekly notes", "Observations from the team", &[]);
+        assert_eq!(
+            classify_urgency(&FlattenedText::new(&rec), config()),
+            UrgencyAssessment { temperature: Temperature::Unknown, severity: Severity::Unknown }
+        );
+
+        // Indicators give a baseline even without a cue.
+        let mut rec = record("Weekly notes", "Observations from the team", &[]);
+        rec.indicators = vec![indicator("", "", None, 0)];
+        assert_eq!(
+            classify_urgency(&FlattenedText::new(&rec), config()),
+            UrgencyAssessment { temperature: Temperature::Cold, severity: Severity::Low }
+        );
+    }
+
+    #[test]
+    fn single_urgency_keyword_overrides_unknown() {
+        for (description, expected) in [
+            ("A minor issue", Severity::Low),
+            ("A moderate issue", Severity::Medium),
+            ("An urgent issue", Severity::Critical),
+        ] {
+            let rec = record("Weekly notes", description, &[]);
+            assert_eq!(classify_urgency(&FlattenedText::new(&rec), config()).severity, expected, "{description}");
+        }
+    }
+
+    #[test]
+    fn unknown_urgency_round_trips() {
+        let urgency = UrgencyAssessment { temperature: Temperature::Unknown, severity: Severity::Unknown };
+        let json = serde_json::to_value(urgency).unwrap();
+        assert_eq!(json, serde_json::json!({ "temperature": "Unknown", "severity": "Unknown" }));
+        assert_eq!(serde_json::from_value::<UrgencyAssessment>(json).unwrap(), urgency);
+        assert!(Temperature::Unknown < Temperature::Cold);
+    }
+
+    #[test]
+    fn extra_attack_keywords_param_extends_the_table() {
+        let config = EnricherConfig::from_params(&params(&[(
+            "extra_attack_keywords",
+            r#"{"Cobalt-Strike": "Trojan", "beacon": "NotAType"}"#,
+        )]))
+        .unwrap();
+        assert_eq!(config.extra_attack_keywords.get("cobalt strike"), Some(&AttackType::Trojan));
+        assert!(!config.extra_attack_keywords.contains_key("beacon"));
+    }
+
+    // The only test that runs init, which can happen once per process.
+    #[test]
+    fn init_adds_extra_attack_keywords_to_the_classifier() {
+        let rec = record("Intrusion report", "Operators deployed cobalt strike on the hosts", &[]);
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Unknown]);
+
+        init(params(&[("extra_attack_keywords", r#"{"Cobalt-Strike": "Trojan"}"#)])).unwrap();
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Trojan]);
+        assert!(!attack_type_keywords().contains_key("cobalt strike"));
+        assert!(init(params(&[])).is_err());
+    }
+
+    #[test]
+    fn malformed_extra_attack_keywords_param_is_an_error() {
+        assert!(EnricherConfig::from_params(&params(&[("extra_attack_keywords", "cobalt strike=Trojan")])).is_err());
+    }
+
+    #[test]
+    fn valid_techniques_map_to_tactics() {
+        let rec = with_attack_ids(&["T1566", "garbage", "T1059.001"]);
+        let techniques = mitre_techniques(&rec);
+        let ids: Vec<&str> = techniques.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["T1566", "T1059.001"]);
+        assert_eq!(mitre_tactics(&techniques), vec!["Initial Access", "Execution"]);
+
+        let rec = with_attack_ids(&["T1059", "T1059.001", "T9999"]);
+        assert_eq!(mitre_tactics(&mitre_techniques(&rec)), vec!["Execution"]);
+    }
+
+    #[test]
+    fn wiper_pulses_classify_as_wiper() {
+        for (name, description) in [
+            ("HermeticWiper", "Destructive malware hitting Ukrainian organisations"),
+            ("Shamoon returns", "Disk wiping attack against energy firms"),
+            ("Campaign", "The payload performs data destruction before exiting"),
+        ] {
+            let rec = record(name, description, &[]);
+            let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+            assert!(types.contains(&AttackType::Wiper), "{name}: {:?}", types);
+        }
+
+        for description in ["Disk wipe via Shamoon", "KillDisk variant", "MegaDeleter dropped on file servers"] {
+            let rec = record("Campaign", description, &[]);
+            let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(types, vec![AttackType::Wiper], "{description}");
+        }
+
+        let mut rec = record("Campaign", "", &[]);
+        rec.malware_families = vec!["WhisperGate".to_string()];
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Wiper]);
+        let rec = with_attack_ids(&["T1561.002"]);
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Wiper]);
+    }
+
+    #[test]
+    fn mining_pulses_classify_as_cryptojacking() {
+        for description in [
+            "XMRig deployed on exposed docker hosts",
+            "Coinhive script injected into compromised sites",
+            "A monero miner dropped by the loader",
+            "Cryptominer spreading over ssh",
+        ] {
+            let rec = record("Campaign", description, &[]);
+            let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+            assert!(types.contains(&AttackType::Cryptojacking), "{description}: {:?}", types);
+            assert!(!types.contains(&AttackType::Malware), "{description}: {:?}", types);
+        }
+    }
+
+    #[test]
+    fn new_attack_types_serialize_with_stable_names() {
+        assert_eq!(serde_json::to_value(AttackType::Wiper).unwrap(), serde_json::json!("Wiper"));
+        assert_eq!(serde_json::to_value(AttackType::Cryptojacking).unwrap(), serde_json::json!("Cryptojacking"));
+        assert_eq!(serde_json::to_value(AttackType::WebSkimming).unwrap(), serde_json::json!("WebSkimming"));
+        assert_eq!(serde_json::to_value(AttackType::Defacement).unwrap(), serde_json::json!("Defacement"));
+        assert_eq!(serde_json::from_value::<AttackType>(serde_json::json!("Wiper")).unwrap(), AttackType::Wiper);
+        assert_eq!(serde_json::from_value::<AttackType>(serde_json::json!("Cryptomining")).unwrap(), AttackType::Cryptojacking);
+    }
+
+    #[test]
+    fn malware_families_survive_and_classify() {
+        let mut rec = record("", "", &[]);
+        rec.malware_families = vec!["WannaCry".to_string(), "Emotet".to_string(), "wannacry ".to_string()];
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.malware_families, vec!["emotet".to_string(), "wannacry".to_string()]);
+        assert_eq!(labels(&enriched.attack_types), vec![AttackType::Ransomware, AttackType::Trojan]);
+    }
+
+    #[test]
+    fn tags_are_normalized_and_capped() {
+        let rec = record("", "", &["Phishing", " phishing ", "APT", "", "  ", "apt"]);
+        let enriched = enrich_record(&rec);
+        assert_eq!(enriched.tags, ["apt", "phishing"]);
+        assert_eq!(serde_json::to_value(enrich_record(&record("", "", &[]))).unwrap()["tags"], serde_json::json!([]));
+
+        let many: Vec<String> = (0..80).map(|i| format!("Tag {i:02}")).collect();
+        let names = normalized_names(&many);
+        assert_eq!(names.len(), MAX_NAMES);
+        assert_eq!(names[0], "tag 00");
+        assert_eq!(names[MAX_NAMES - 1], "tag 49");
+    }
+
+    #[test]
+    fn family_name_endings_bias_attack_type() {
+        assert_eq!(malware_family_type("DarkLocker"), Some(AttackType::Ransomware));
+        assert_eq!(malware_family_type("Dark-Locker 2.1"), Some(AttackType::Ransomware));
+        assert_eq!(malware_family_type("ShadowWiper"), Some(AttackType::Wiper));
+        assert_eq!(malware_family_type("GhostMiner"), Some(AttackType::Cryptojacking));
+        assert_eq!(malware_family_type("NewLoader"), None);
+    }
+
+    #[test]
+    fn asyncrat_pulse_is_a_rat_not_a_trojan() {
+        let rec = record("AsyncRAT campaign", "AsyncRAT delivered through malicious shortcuts", &[]);
+        let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(types, vec![AttackType::RemoteAccessTrojan]);
+
+        let mut rec = record("", "", &[]);
+        rec.malware_families = vec!["njRAT".to_string(), "SnakeRAT".to_string()];
+        let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(types, vec![AttackType::RemoteAccessTrojan]);
+    }
+
+    #[test]
+    fn stealers_are_info_stealers() {
+        for description in [
+            "Lumma distributed through fake captcha pages",
+            "A credential stealer harvesting browser passwords",
+            "RedLine sold on underground forums",
+        ] {
+            let rec = record("Campaign", description, &[]);
+            let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(types, vec![AttackType::InfoStealer], "{description}");
+        }
+        assert_eq!(malware_family_type("Raccoon Stealer v2"), Some(AttackType::InfoStealer));
+    }
+
+    #[test]
+    fn droppers_and_backdoors_stay_trojans() {
+        let rec = record("", "A dropper installs a backdoor", &[]);
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Trojan]);
+    }
+
+    #[test]
+    fn rat_needs_word_boundaries() {
+        let rec = record("", "A new strategy for migration", &[]);
+        let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+        assert!(!types.contains(&AttackType::RemoteAccessTrojan));
+    }
+
+    #[test]
+    fn bec_pulses_classify_as_business_email_compromise() {
+        for (name, description) in [
+            ("BEC actors target finance teams", "Payroll diversion requests sent from lookalike domains"),
+            ("CEO fraud wave", "Attackers impersonate executives to push urgent wire transfer fraud"),
+            ("Invoice fraud", "Compromised supplier mailboxes used to redirect payments"),
+        ] {
+            let rec = record(name, description, &[]);
+            let text = FlattenedText::new(&rec);
+            let types = labels(&classify_attack_types(&text, THRESHOLD));
+            assert!(types.contains(&AttackType::BusinessEmailCompromise), "{name}: {:?}", types);
+            assert!(!types.contains(&AttackType::Phishing), "{name}: {:?}", types);
+        }
+    }
+
+    #[test]
+    fn bec_and_phishing_co_occur_with_email_vector_and_target() {
+        let rec = record(
+            "Business email compromise",
+            "Spearphishing emails harvest mailbox credentials before invoice fraud",
+            &[],
+        );
+        let text = FlattenedText::new(&rec);
+        let types = labels(&classify_attack_types(&text, THRESHOLD));
+        assert!(types.contains(&AttackType::Phishing));
+        assert!(types.contains(&AttackType::BusinessEmailCompromise));
+        assert_eq!(labels(&classify_attack_vectors(&text, THRESHOLD)), vec![AttackVector::Email]);
+        assert!(labels(&classify_targets(&text, THRESHOLD)).contains(&Target::EmailAttack));
+
+        let rec = record("BEC", "", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_attack_vectors(&text, THRESHOLD)), vec![AttackVector::Email]);
+        assert_eq!(labels(&classify_targets(&text, THRESHOLD)), vec![Target::EmailAttack]);
+    }
+
+    #[test]
+    fn compound_industries_name_every_sector() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.industries = vec!["Healthcare and Banking".to_string()];
+        assert_eq!(classify_industries(&rec), vec![Industry::Finance, Industry::Healthcare]);
+        let (sectors, raw_industries) = classify_sectors(&FlattenedText::new(&rec));
+        assert_eq!(sectors, vec![Sector::Finance, Sector::Healthcare]);
+        assert!(raw_industries.is_empty());
+    }
+
+    #[test]
+    fn industries_ignore_the_description() {
+        let mut rec = record("Campaign", "The group targets hospitals", &[]);
+        rec.industries = vec!["Banks".to_string(), "Shipping".to_string(), "Finance".to_string()];
+        assert_eq!(classify_industries(&rec), vec![Industry::Finance, Industry::Other]);
+
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.industries, vec![Industry::Finance, Industry::Other]);
+        assert_eq!(enriched.sectors, vec![Sector::Finance, Sector::Healthcare, Sector::Other]);
+    }
+
+    #[test]
+    fn exploit_keywords_classify_without_cve() {
+        let rec = record("", "Unauthenticated remote code execution in the gateway", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Exploit]);
+
+        let rec = record("", "A proof of concept exploit was published", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Exploit]);
+    }
+
+    #[test]
+    fn lone_exploit_needs_corroboration() {
+        let rec = record("", "Scammers exploit the holiday shopping season", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+
+        let rec = record("", "Attackers exploit CVE-2017-11882 in Office", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Exploit]);
+    }
+
+    #[test]
+    fn dns_hijacking_keywords_classify() {
+        let rec = record("", "Registrar accounts abused for a nameserver compromise", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::DnsHijacking]);
+        assert_eq!(serde_json::to_value(AttackType::DnsHijacking).unwrap(), serde_json::json!("dns_hijacking"));
+        assert_eq!(serde_json::from_value::<AttackType>(serde_json::json!("DnsHijacking")).unwrap(), AttackType::DnsHijacking);
+    }
+
+    #[test]
+    fn typosquatting_keywords_classify() {
+        let rec = record("", "Typosquatted packages published to the registry", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Typosquatting]);
+        assert_eq!(serde_json::to_value(AttackType::Typosquatting).unwrap(), serde_json::json!("typosquatting"));
+        assert_eq!(serde_json::from_value::<AttackType>(serde_json::json!("Typosquatting")).unwrap(), AttackType::Typosquatting);
+    }
+
+    #[test]
+    fn lookalike_domain_indicators_suggest_typosquatting() {
+        let mut rec = record("", "", &[]);
+        rec.indicators = vec![
+            ioc_indicator("domain", "paypa1.com", 1),
+            ioc_indicator("hostname", "login.micros0ft-online.net", 1),
+            ioc_indicator("hostname", "secure.gooogle.com", 1),
+            ioc_indicator("domain", "evil.example", 1),
+        ];
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Typosquatting]);
+
+        rec.indicators = vec![
+            ioc_indicator("domain", "paypal.com", 1),
+            ioc_indicator("domain", "paypa1.com", 1),
+            ioc_indicator("domain", "evil.example", 1),
+            ioc_indicator("domain", "c2.example", 1),
+        ];
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Unknown]);
+    }
+
+    #[test]
+    fn watering_hole_keywords_classify() {
+        let rec = record("", "A strategic web compromise of regional news sites", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::WateringHole]);
+        assert_eq!(serde_json::to_value(AttackType::WateringHole).unwrap(), serde_json::json!("watering_hole"));
+        assert_eq!(serde_json::from_value::<AttackType>(serde_json::json!("WateringHole")).unwrap(), AttackType::WateringHole);
+    }
+
+    #[test]
+    fn scanning_pulses_classify_as_reconnaissance() {
+        let rec = record("", "Widespread port scanning and nmap probing", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_attack_types(&text, THRESHOLD)), vec![AttackType::Reconnaissance]);
+        assert_eq!(labels(&classify_attack_vectors(&text, THRESHOLD)), vec![AttackVector::Network]);
+
+        let rec = record("", "Masscan sweeps of exposed RDP", &[]);
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Reconnaissance]);
+        let rec = with_attack_ids(&["T1595.002"]);
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Reconnaissance]);
+
+        let rec = record("", "The loader performs enumeration of installed software", &[]);
+        assert_eq!(labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD)), vec![AttackType::Unknown]);
+    }
+
+    #[test]
+    fn active_ransomware_is_critical() {
+        let mut rec = record("LockBit ransomware campaign", "", &[]);
+        rec.indicators = vec![indicator("", "", None, 1)];
+        assert_eq!(overall_severity(&rec), "Critical");
+
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.overall_severity, "Critical");
+    }
+
+    #[test]
+    fn pulse_without_indicators_is_info() {
+        let rec = record("Weekly roundup", "Notes from the week", &[]);
+        assert_eq!(overall_severity(&rec), "Info");
+
+        let mut rec = record("LockBit ransomware campaign", "", &[]);
+        assert_eq!(overall_severity(&rec), "High");
+        rec.name = "Weekly roundup".to_string();
+        rec.indicators = vec![indicator("", "", None, 1)];
+        assert_eq!(overall_severity(&rec), "Low");
+    }
+
+    #[test]
+    fn skimming_pulses_imply_web_vector_and_target() {
+        for description in [
+            "Magecart group injects a payment page script",
+            "Formjacking on checkout pages",
+            "An e-skimming campaign against online stores",
+        ] {
+            let rec = record("Campaign", description, &[]);
+            let text = FlattenedText::new(&rec);
+            assert_eq!(labels(&classify_attack_types(&text, THRESHOLD)), vec![AttackType::WebSkimming], "{description}");
+            assert_eq!(labels(&classify_attack_vectors(&text, THRESHOLD)), vec![AttackVector::WebApplication], "{description}");
+            assert!(labels(&classify_targets(&text, THRESHOLD)).contains(&Target::WebApp), "{description}");
+        }
+    }
+
+    #[test]
+    fn defacement_pulses_classify_as_defacement() {
+        for description in ["Hacktivists defaced government portals", "A wave of website defacement"] {
+            let rec = record("Campaign", description, &[]);
+            let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(types, vec![AttackType::Defacement], "{description}");
+        }
+
+        let rec = record("Campaign", "Mass website defacement by hacktivists", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_attack_types(&text, THRESHOLD)), vec![AttackType::Defacement]);
+        assert_eq!(labels(&classify_attack_vectors(&text, THRESHOLD)), vec![AttackVector::WebApplication]);
+    }
+
+    #[test]
+    fn removable_media_vector_classifies() {
+        let rec = record("", "The worm spreads over USB drives using autorun", &[]);
+        let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(vectors, vec![AttackVector::RemovableMedia]);
+
+        let rec = record("", "Substation busbar monitoring", &[]);
+        let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(vectors, vec![AttackVector::Unknown]);
+    }
+
+    #[test]
+    fn remote_access_vector_classifies() {
+        for description in ["Exposed RDP servers", "Compromised VPN appliance", "SSH brute force against honeypots"] {
+            let rec = record("", description, &[]);
+            let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(vectors, vec![AttackVector::RemoteAccess], "{description}");
+        }
+
+        let rec = record("", "Vulnerable WordPress plugin", &[]);
+        let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+        assert!(!vectors.contains(&AttackVector::RemoteAccess));
+    }
+
+    #[test]
+    fn drive_by_vector_classifies() {
+        for description in ["Drive-by downloads from compromised sites", "RIG exploit kit", "A malvertising landing page"] {
+            let rec = record("", description, &[]);
+            let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+            assert!(vectors.contains(&AttackVector::DriveByDownload), "{description}: {:?}", vectors);
+        }
+    }
+
+    fn enriched_fixture() -> EnrichedThreatRecord {
+        let mut rec = record("LockBit ransomware", "Phishing emails deliver the loader", &[]);
+        rec.targeted_countries = vec!["Korea, Republic of".to_string(), "Japan".to_string()];
+        rec.adversary = "Group \"Alpha\"".to_string();
+        let output = run_array_map(&pulse(vec![rec]));
+        serde_json::from_slice(output[0].1.as_ref()).unwrap()
+    }
+
+    /// Splits one CSV line into fields, honouring quotes.
+    fn split_csv(line: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn csv_row_matches_header() {
+        let enriched = enriched_fixture();
+        let header: Vec<&str> = EnrichedThreatRecord::csv_header().split(',').collect();
+        let row = split_csv(&enriched.to_csv_row());
+        assert_eq!(row.len(), header.len());
+
+        let column = |name: &str| row[header.iter().position(|column| *column == name).unwrap()].clone();
+        assert_eq!(column("source_id"), "pulse-1");
+        assert_eq!(column("attack_types"), "Ransomware|Phishing");
+        assert_eq!(column("locations"), "Korea, Republic of|Japan");
+        assert_eq!(column("threat_actor"), "Group \"Alpha\"");
+    }
+
+    #[test]
+    fn csv_fields_with_commas_are_quoted() {
+        let row = enriched_fixture().to_csv_row();
+        assert!(row.contains(",\"Korea, Republic of|Japan\","));
+        assert!(row.contains(",\"Group \"\"Alpha\"\"\","));
+        assert_eq!(csv_field("plain"), "plain");
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trips_and_is_smaller_than_json() {
+        let mut enriched = enriched_fixture();
+        enriched.tlp = Tlp::AmberStrict;
+        let bytes = enriched.to_bincode().unwrap();
+        assert_eq!(EnrichedThreatRecord::from_bincode(&bytes).unwrap(), enriched);
+        assert!(bytes.len() < serde_json::to_vec(&enriched).unwrap().len());
+
+        enriched.evidence = Some(vec![]);
+        let bytes = enriched.to_bincode().unwrap();
+        assert_eq!(EnrichedThreatRecord::from_bincode(&bytes).unwrap(), enriched);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_array_map_emits_bincode() {
+        let output = array_map_bincode(&SmartModuleRecord::new(
+            fluvio_smartmodule::Record::new(serde_json::to_vec(&pulse(vec![record("LockBit ransomware", "", &[])])).unwrap()),
+            0,
+            0,
+        ))
+        .unwrap();
+        let enriched = EnrichedThreatRecord::from_bincode(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.source_id, "pulse-1");
+    }
+
+    #[test]
+    fn smishing_is_phishing_over_messaging() {
+        let rec = record("", "A smishing wave impersonating parcel carriers", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_attack_types(&text, THRESHOLD)), vec![AttackType::Phishing]);
+        assert_eq!(labels(&classify_attack_vectors(&text, THRESHOLD)), vec![AttackVector::Messaging]);
+
+        let rec = record("", "Telegram lure alongside phishing email", &[]);
+        let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+        assert!(vectors.contains(&AttackVector::Messaging) && vectors.contains(&AttackVector::Email), "{:?}", vectors);
+    }
+
+    #[test]
+    fn dns_vector_classifies() {
+        for description in ["Data leaves over DNS tunneling", "The implant uses a DGA for C2"] {
+            let rec = record("", description, &[]);
+            let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(vectors, vec![AttackVector::Dns], "{description}");
+        }
+    }
+
+    #[test]
+    fn malvertising_vector_classifies() {
+        let rec = record("", "Malicious ads for fake browser updates", &[]);
+        let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(vectors, vec![AttackVector::Malvertising]);
+
+        let rec = record("", "Malvertising leads to a drive-by download", &[]);
+        let vectors = labels(&classify_attack_vectors(&FlattenedText::new(&rec), THRESHOLD));
+        assert!(vectors.contains(&AttackVector::Malvertising) && vectors.contains(&AttackVector::DriveByDownload), "{:?}", vectors);
+    }
+
+    #[test]
+    fn identical_results_are_emitted_once() {
+        let rec = record("LockBit ransomware", "Phishing emails deliver the loader", &[]);
+        let mut other = rec.clone();
+        other.id = "pulse-2".to_string();
+        let output = run_array_map(&pulse(vec![rec.clone(), rec.clone(), other, rec]));
+        let ids: Vec<&[u8]> = output.iter().map(|(key, _)| key.as_ref().unwrap().as_ref()).collect();
+        assert_eq!(ids, vec![b"pulse-1".as_slice(), b"pulse-2".as_slice()]);
+    }
+
+    #[test]
+    fn mobile_targets_classify() {
+        for description in ["Android banking app overlay", "Spyware for iOS", "Sideloaded APK files"] {
+            let rec = record("", description, &[]);
+            let targets = labels(&classify_targets(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(targets, vec![Target::MobileDevices], "{description}");
+        }
+        for description in ["Firmware implant in the BIOS", "Kiosk software update"] {
+            let rec = record("", description, &[]);
+            let targets = labels(&classify_targets(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(targets, vec![Target::Unknown], "{description}");
+        }
+    }
+
+    #[test]
+    fn database_targets_classify() {
+        for description in ["Brute forcing exposed MSSQL instances", "Ransom notes left in MongoDB"] {
+            let rec = record("", description, &[]);
+            let targets = labels(&classify_targets(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(targets, vec![Target::DatabaseSystems], "{description}");
+        }
+    }
+
+    #[test]
+    fn point_of_sale_targets_classify() {
+        for description in ["Point-of-sale malware scraping card data", "Compromised payment terminals"] {
+            let rec = record("", description, &[]);
+            let targets = labels(&classify_targets(&FlattenedText::new(&rec), THRESHOLD));
+            assert_eq!(targets, vec![Target::PointOfSale], "{description}");
+        }
+    }
+
+    #[test]
+    fn fixture_targets_are_unchanged() {
+        let targets: Vec<Vec<Target>> = fixture_records()
+            .iter()
+            .map(|rec| labels(&classify_targets(&FlattenedText::new(rec), THRESHOLD)))
+            .collect();
+        assert_eq!(targets, vec![
+            vec![Target::IotDevices],
+            vec![Target::EmailAttack],
+            vec![Target::Unknown],
+            vec![Target::ApiAbuse],
+        ]);
+    }
+
+    #[test]
+    fn summarize_rolls_up_every_result() {
+        let mut ransomware = record("LockBit ransomware", "Encrypts database servers", &[]);
+        ransomware.id = "pulse-2".to_string();
+        ransomware.targeted_countries = vec!["Japan".to_string(), "Germany".to_string()];
+        ransomware.indicators = vec![indicator("", "", None, 1), indicator("", "", None, 1), indicator("", "", None, 0)];
+        let mut phishing = record("Invoice lure", "Critical phishing campaign sending malicious email", &[]);
+        phishing.targeted_countries = vec!["Germany".to_string(), "Brazil".to_string()];
+        phishing.indicators = vec![indicator("", "", None, 1)];
+        let quiet = record("Weekly roundup", "", &[]);
+
+        let value = serde_json::to_vec(&pulse(vec![ransomware, phishing, quiet])).unwrap();
+        let (key, summary) = summarize(&SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0)).unwrap();
+        let summary: PulseSummary = serde_json::from_slice(summary.as_ref()).unwrap();
+        assert!(key.is_none());
+        assert_eq!(summary.total_results, 3);
+        assert_eq!(summary.distinct_attack_types, vec![AttackType::Ransomware, AttackType::Phishing]);
+        assert_eq!(summary.distinct_targets, vec![Target::EmailAttack, Target::DatabaseSystems]);
+        assert_eq!(summary.max_urgency.severity, Severity::Critical);
+        assert_eq!(summary.total_active_indicators, 3);
+        assert_eq!(summary.all_countries, vec!["Japan", "Germany", "Brazil"]);
+        assert_eq!((summary.total_count, summary.has_next, summary.has_previous), (3, false, false));
+    }
+
+    #[test]
+    fn summarize_reports_pagination() {
+        let mut page = pulse(vec![record("LockBit ransomware", "", &[])]);
+        page.count = 240;
+        page.next = Some("https://otx.alienvault.com/api/v1/pulses/subscribed?page=2".to_string());
+        let value = serde_json::to_vec(&page).unwrap();
+        let (_, summary) = summarize(&SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0)).unwrap();
+        let summary: PulseSummary = serde_json::from_slice(summary.as_ref()).unwrap();
+        assert_eq!(summary.total_results, 1);
+        assert_eq!(summary.total_count, 240);
+        assert!(summary.has_next);
+        assert!(!summary.has_previous);
+
+        let value = serde_json::to_vec(&serde_json::json!({ "results": [{ "id": 7 }], "count": 9, "previous": "p1" })).unwrap();
+        let (results, page) = parse_results(&value);
+        assert!(results[0].is_err());
+        assert_eq!((page.count, page.next.is_some(), page.previous.is_some()), (Some(9), false, true));
+        assert!(parse_results(b"{").1.count.is_none());
+    }
+
+    #[test]
+    fn os_targets_cover_every_platform_named() {
+        let rec = record("Cross-platform loader", "Ships Windows PE, Linux ELF and macOS Mach-O payloads", &[]);
+        assert_eq!(classify_os_targets(&FlattenedText::new(&rec)), vec![OsTarget::Windows, OsTarget::Linux, OsTarget::MacOS]);
+
+        let rec = record("Mobile spyware", "Targets Android and iOS handsets", &[]);
+        assert_eq!(classify_os_targets(&FlattenedText::new(&rec)), vec![OsTarget::Android, OsTarget::Ios]);
+
+        let rec = record("Campaign", "Sensors in the windowsill, a kiosk and a BIOS update", &[]);
+        assert_eq!(classify_os_targets(&FlattenedText::new(&rec)), vec![OsTarget::Unknown]);
+    }
+
+    #[test]
+    fn file_names_of_hash_indicators_hint_at_os() {
+        let mut rec = record("Campaign", "Botnet targeting routers", &[]);
+        rec.indicators = vec![
+            ioc_indicator("FileHash-SHA256", "a1b2", 1),
+            ioc_indicator("FileHash-MD5", "c3d4", 1),
+            ioc_indicator("URL", "https://evil.example/update.apk", 1),
+        ];
+        rec.indicators[0].title = "invoice.exe".to_string();
+        rec.indicators[1].content = "dropped as libupdate.so".to_string();
+        rec.indicators[2].content = "update.apk".to_string();
+        assert_eq!(classify_os_targets(&FlattenedText::new(&rec)), vec![OsTarget::Windows, OsTarget::Linux, OsTarget::Embedded]);
+
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(enriched.os_targets, vec![OsTarget::Windows, OsTarget::Linux, OsTarget::Embedded]);
+    }
+
+    #[test]
+    fn empty_pulse_emits_sentinel_only_when_enabled() {
+        let value = serde_json::to_vec(&pulse(vec![])).unwrap();
+        let record = SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0);
+        let encode = |enriched: &EnrichedThreatRecord| Ok(to_vec(enriched)?);
+
+        let config = EnricherConfig::from_params(&params(&[])).unwrap();
+        assert!(enrich_records(&record, &config, encode).unwrap().is_empty());
+
+        let config = EnricherConfig::from_params(&params(&[("emit_empty", "true")])).unwrap();
+        let output = enrich_records(&record, &config, encode).unwrap();
+        assert_eq!(output.len(), 1);
+        assert!(output[0].0.is_none());
+        let sentinel: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(sentinel.source_id, EMPTY_PULSE_SOURCE_ID);
+        assert_eq!(labels(&sentinel.attack_types), vec![AttackType::Unknown]);
+        assert_eq!(sentinel.urgency.severity, Severity::Unknown);
+
+        assert!(EnricherConfig::from_params(&params(&[("emit_empty", "sometimes")])).is_err());
+    }
+
+    #[test]
+    fn ddos_amplification_has_no_stray_dos_hit() {
+        let hits = attack_type_matcher().hits("ddos amplification attack");
+        let keywords: Vec<&str> = hits.iter().map(|hit| hit.keyword).collect();
+        assert_eq!(keywords, vec!["ddos", "amplification attack"]);
+
+        let rec = record("", "ddos amplification attack", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ddos]);
+    }
+
+    #[test]
+    fn overlapping_keywords_count_once() {
+        let hits = attack_type_matcher().hits("a sql injection attack on the login form");
+        assert_eq!(hits.len(), 1, "{:?}", hits);
+        assert_eq!(hits[0].keyword, "sql injection");
+
+        let hits = attack_type_matcher().hits("a remote access trojan");
+        assert_eq!(hits.iter().map(|hit| hit.label).collect::<Vec<_>>(), vec![AttackType::RemoteAccessTrojan]);
+    }
+
+    #[test]
+    fn enrichment_output_is_byte_identical_across_runs() {
+        let mut rec = record(
+            "Trojan and ransomware over phishing email",
+            "A botnet loader drops a RAT and a stealer, exfiltrating over DNS tunneling from Windows and Linux hosts",
+            &["Malware", "Phishing"],
+        );
+        rec.industries = vec!["Healthcare and Banking".to_string(), "Energy".to_string()];
+        let input = pulse(vec![rec]);
+        let first = run_array_map(&input);
+        for _ in 0..100 {
+            assert_eq!(run_array_map(&input), first);
+        }
+    }
+
+    #[test]
+    fn classifications_are_in_declaration_order() {
+        let rec = record("", "Phishing emails deliver ransomware", &[]);
+        let types = labels(&classify_attack_types(&FlattenedText::new(&rec), THRESHOLD));
+        assert_eq!(types, vec![AttackType::Ransomware, AttackType::Phishing]);
+
+        let mut rec = record("", "", &[]);
+        rec.industries = vec!["Banking and Healthcare".to_string()];
+        assert_eq!(classify_industries(&rec), vec![Industry::Finance, Industry::Healthcare]);
+    }
+
+    #[test]
+    fn phishing_implies_email_vector_and_target() {
+        let rec = record("Credential phishing", "Lure pages impersonate a bank login", &[]);
+        let text = FlattenedText::new(&rec);
+        assert_eq!(labels(&classify_targets(&text, THRESHOLD)), vec![Target::Unknown]);
+
+        let output = run_array_map(&pulse(vec![rec]));
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(labels(&enriched.attack_types), vec![AttackType::Phishing]);
+        assert_eq!(labels(&enriched.attack_vectors), vec![AttackVector::Email]);
+        assert_eq!(labels(&enriched.targets), vec![Target::EmailAttack]);
+        assert_eq!(enriched.dominant_vector, AttackVector::Email);
+    }
+
+    #[test]
+    fn classifies_spanish_pulses() {
+        let rec = record("", "Campaña de secuestro de datos distribuida con un troyano", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Trojan]);
+    }
+
+    #[test]
+    fn classifies_portuguese_pulses() {
+        let rec = record("", "Ataques de negação de serviço lançados por uma rede de bots", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ddos, AttackType::Botnet]);
+    }
+
+    #[test]
+    fn classifies_french_pulses() {
+        let rec = record("", "Une campagne d'HAMEÇONNAGE précède le déploiement d'un rançongiciel", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Phishing]);
+    }
+
+    #[test]
+    fn classifies_german_pulses() {
+        let rec = record("", "Ein Erpressungstrojaner verbreitet sich über ein Botnetz", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Botnet]);
+    }
+
+    #[test]
+    fn classifies_russian_pulses() {
+        let rec = record("", "Фишинговая рассылка доставляет шифровальщик и троян", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware, AttackType::Phishing, AttackType::Trojan]);
+    }
+
+    #[test]
+    fn prune_unknown_keeps_only_real_labels() {
+        assert_eq!(prune_unknown(vec![AttackType::Unknown, AttackType::Malware], AttackType::Unknown), vec![AttackType::Malware]);
+        assert_eq!(prune_unknown(vec![AttackType::Unknown], AttackType::Unknown), vec![AttackType::Unknown]);
+        let targets = vec![Scored::unknown(Target::Unknown), Scored { label: Target::WebApp, confidence: 0.5 }];
+        assert_eq!(labels(&prune_unknown(targets, Scored::unknown(Target::Unknown))), vec![Target::WebApp]);
+    }
+
+    #[test]
+    fn matching_ignores_ascii_case() {
+        let hits = attack_type_matcher().hits("A RaNsOmWaRe outbreak, NOT a DDoS");
+        assert_eq!(hits.iter().map(|hit| hit.label).collect::<Vec<_>>(), vec![AttackType::Ransomware]);
+
+        let rec = record("", "RaNsOmWaRe", &[]);
+        let types = classify_attack_types(&FlattenedText::new(&rec), THRESHOLD);
+        assert_eq!(labels(&types), vec![AttackType::Ransomware]);
+    }
+
+    #[test]
+    fn default_keyword_config_matches_built_in_tables() {
+        let config = KeywordConfig::default();
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(KeywordConfig::from_json(&json).unwrap(), config);
+        let matchers = config.matchers();
+        for record in fixture_records() {
+            let text = normalize(&format!("{} {}", record.name, record.description));
+            assert_eq!(matchers.attack_types.hits(&text), attack_type_matcher().hits(&text));
+            assert_eq!(matchers.attack_vectors.hits(&text), attack_vector_matcher().hits(&text));
+            assert_eq!(matchers.targets.hits(&text), target_matcher().hits(&text));
+            assert_eq!(matchers.urgency.hits(&text), urgency_matcher().hits(&text));
+        }
+    }
+
+    #[test]
+    fn keyword_config_replaces_built_in_keywords() {
+        let config = KeywordConfig::from_json(
+            r#"{
+                "attack_types": [{"keyword": "Cobalt-Strike", "label": "Trojan"}, {"keyword": "stealer", "label": "Trojan", "weight": 0.4}],
+                "attack_vectors": [],
+                "targets": [{"keyword": "vpn", "label": "Infrastructure"}],
+                "urgency": [{"keyword": "exploited in the wild", "label": "Critical"}]
+            }"#,
+        )
+        .unwrap();
+        let matchers = config.matchers();
+        let text = normalize("Cobalt Strike beacon and a stealer dropped by ransomware via the VPN, exploited in the wild");
+        let hits = matchers.attack_types.hits(&text);
+        assert_eq!(hits.iter().map(|hit| (hit.keyword, hit.weight)).collect::<Vec<_>>(), [("cobalt strike", STRONG), ("stealer", WEAK)]);
+        assert!(matchers.attack_vectors.hits(&text).is_empty());
+        assert_eq!(matchers.targets.find(&[&text]), [Target::Infrastructure]);
+        assert_eq!(matchers.urgency.find(&[&text]), [Urgency::Critical]);
+    }
+
+    #[test]
+    fn keyword_config_keeps_extra_attack_keywords() {
+        let config = KeywordConfig::from_json(
+            r#"{"attack_types": [{"keyword": "stealer", "label": "Trojan"}], "attack_vectors": [], "targets": [], "urgency": []}"#,
+        )
+        .unwrap();
+        let extra = HashMap::from([("cobalt strike", AttackType::Trojan), ("stealer", AttackType::InfoStealer)]);
+        let matchers = config.matchers_with_extras(&extra);
+        let hits = matchers.attack_types.find_all("Cobalt Strike beacon and a stealer");
+        assert_eq!(hits, [AttackType::Trojan, AttackType::InfoStealer]);
+    }
+
+    #[test]
+    fn keyword_config_rejects_invalid_tables() {
+        let table = |attack_types: &str| {
+            format!(r#"{{"attack_types": [{}], "attack_vectors": [], "targets": [], "urgency": []}}"#, attack_types)
+        };
+        for entries in [
+            r#"{"keyword": "beacon", "label": "Backdoor"}"#,
+            r#"{"keyword": " -_ ", "label": "Trojan"}"#,
+            r#"{"keyword": "Info-Stealer", "label": "Trojan"}, {"keyword": "info stealer", "label": "Spyware"}"#,
+            r#"{"keyword": "beacon", "label": "Trojan", "weight": 0}"#,
+        ] {
+            assert!(KeywordConfig::from_json(&table(entries)).is_err(), "{}", entries);
+        }
+        assert!(KeywordConfig::from_json(r#"{"attack_types": []}"#).is_err());
+        assert!(KeywordConfig::from_json(&table(r#"{"keyword": "beacon", "label": "Trojan"}"#)).is_ok());
+    }
+
+    #[test]
+    fn enrich_record_matches_array_map_output() {
+        let mut rec = record("LockBit ransomware", "Phishing emails deliver the loader", &["finance"]);
+        rec.indicators = vec![indicator("evil.example", "", None, 1)];
+        let enriched = enrich_record(&rec);
+        assert_eq!(enriched.source_id, "pulse-1");
+        assert_eq!(labels(&enriched.attack_types), [AttackType::Ransomware, AttackType::Phishing]);
+        assert_eq!(enriched.active_indicator_count, 1);
+
+        let output = run_array_map(&pulse(vec![rec.clone()]));
+        let emitted: EnrichedThreatRecord = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(emitted, enriched);
+        assert_eq!(enrich_pulse(&pulse(vec![rec.clone(), rec])), [enriched.clone(), enriched]);
+    }
+
+    #[test]
+    fn enriched_records_carry_pulse_id_name_and_revision() {
+        let mut first = record("LockBit wave", "ransomware", &[]);
+        first.id = "5f1a".to_string();
+        first.revision = 3;
+        let mut second = first.clone();
+        second.description = "phishing".to_string();
+        let output = run_array_map(&pulse(vec![first, second]));
+        assert_eq!(output.len(), 2);
+        for (_, value) in &output {
+            let json: serde_json::Value = serde_json::from_slice(value.as_ref()).unwrap();
+            assert_eq!(json["pulse_id"], "5f1a");
+            assert_eq!(json["pulse_name"], "LockBit wave");
+            assert_eq!(json["revision"], 3);
+            assert_eq!(json["source_id"], json["pulse_id"]);
+            assert_eq!(json["source_name"], json["pulse_name"]);
+        }
+    }
+
+    #[test]
+    fn builder_makes_a_minimal_record_to_enrich() {
+        let rec = OTXRecord::builder()
+            .with_id("pulse-9")
+            .with_name("Emotet resurgence")
+            .with_description("Phishing emails drop a banking trojan")
+            .with_tags(["botnet"])
+            .with_targeted_countries(["Germany"])
+            .with_adversary("TA542")
+            .with_attack_ids(["T1566"])
+            .with_indicators(vec![indicator("Loader", "", None, 1)])
+            .build();
+        assert!(rec.author_name.is_empty() && rec.references.is_empty());
+
+        let enriched = enrich_record(&rec);
+        assert_eq!(enriched.source_id, "pulse-9");
+        assert!(labels(&enriched.attack_types).contains(&AttackType::Phishing));
+        assert_eq!(enriched.locations, ["Germany"]);
+        assert_eq!(enriched.threat_actor.as_deref(), Some("TA542"));
+        assert_eq!(enriched.mitre_techniques[0].id, "T1566");
+        assert_eq!(enriched.active_indicator_count, 1);
+    }
+
+    #[test]
+    fn key_strategy_picks_the_record_key() {
+        let keys = |strategy: &str, rec: &OTXRecord| -> Option<Vec<u8>> {
+            let config = EnricherConfig::from_params(&params(&[("key_strategy", strategy)])).unwrap();
+            let value = serde_json::to_vec(&pulse(vec![rec.clone()])).unwrap();
+            let record = SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0);
+            let output = enrich_records(&record, &config, |enriched| Ok(to_vec(enriched)?)).unwrap();
+            output[0].0.as_ref().map(|key| key.as_ref().to_vec())
+        };
+        let mut rec = record("LockBit wave", "Critical ransomware outbreak", &[]);
+        rec.id = "64f0a1".to_string();
+        rec.targeted_countries = vec!["Japan".to_string(), "Korea".to_string()];
+        rec.adversary = "Wizard Spider".to_string();
+
+        assert_eq!(keys("pulse_id", &rec).unwrap(), b"64f0a1");
+        assert_eq!(keys("urgency", &rec).unwrap(), b"Critical");
+        assert_eq!(keys("first_country", &rec).unwrap(), b"Japan");
+        assert_eq!(keys("adversary", &rec).unwrap(), b"Wizard Spider");
+        assert_eq!(keys("none", &rec), None);
+
+        let mut bare = record("LockBit wave", "ransomware", &[]);
+        bare.id = "64f0a1".to_string();
+        for strategy in ["urgency", "first_country", "adversary"] {
+            assert_eq!(keys(strategy, &bare).unwrap(), b"64f0a1", "{strategy}");
+        }
+        assert_eq!(EnricherConfig::default().key_strategy, KeyStrategy::PulseId);
+    }
+
+    #[test]
+    fn unknown_key_strategy_param_is_an_error() {
+        let error = EnricherConfig::from_params(&params(&[("key_strategy", "severity")])).unwrap_err();
+        assert!(error.to_string().contains("key_strategy must be one of"), "{error}");
+    }
+
+    #[test]
+    fn pulse_timestamps_are_normalized_when_they_parse() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.created = "2024-01-01T12:00:00.123456".to_string();
+        rec.modified = "2024-01-02T09:30:00+02:00".to_string();
+        let enriched = enrich_record(&rec);
+        assert_eq!(enriched.created, "2024-01-01T12:00:00Z");
+        assert_eq!(enriched.modified, "2024-01-02T07:30:00Z");
+        assert!(enriched.age_hours.is_some());
+
+        rec.created = "last tuesday".to_string();
+        rec.modified = "".to_string();
+        let enriched = enrich_record(&rec);
+        assert_eq!(enriched.created, "last tuesday");
+        assert_eq!(enriched.modified, "");
+        assert_eq!(enriched.age_hours, None);
+
+        let now = parse_iso8601("2024-01-03T08:00:00").unwrap();
+        assert_eq!(age_hours("2024-01-02T07:30:00Z", now), Some(24));
+        assert_eq!(age_hours("2024-01-04T00:00:00", now), Some(0));
+        assert_eq!(age_hours("garbage", now), None);
+    }
+
+    #[test]
+    fn references_keep_distinct_http_urls_up_to_the_cap() {
+        let mut rec = record("", "", &[]);
+        rec.references = vec![
+            "https://example.com/report".to_string(),
+            " https://example.com/report ".to_string(),
+            "HTTP://vendor.example/blog?id=7".to_string(),
+            "see the vendor blog".to_string(),
+            "ftp://example.com/iocs.txt".to_string(),
+            "https://".to_string(),
+            "https://example.com/a b".to_string(),
+            "".to_string(),
+        ];
+        let (references, dropped) = extract_references(&rec, DEFAULT_MAX_REFERENCES);
+        assert_eq!(references, ["https://example.com/report", "HTTP://vendor.example/blog?id=7"]);
+        assert_eq!(dropped, 5);
+
+        rec.references = (0..30).map(|i| format!("https://example.com/{i}")).collect();
+        let (references, dropped) = extract_references(&rec, DEFAULT_MAX_REFERENCES);
+        assert_eq!(references.len(), 20);
+        assert_eq!(references[19], "https://example.com/19");
+        assert_eq!(dropped, 0);
+        let config = EnricherConfig::from_params(&params(&[("max_references", "3")])).unwrap();
+        assert_eq!(enrich_record_with(&rec, &config).references.len(), 3);
+    }
+
+    #[test]
+    fn dropped_references_are_counted_with_evidence() {
+        let mut rec = record("", "", &[]);
+        rec.references = vec!["https://example.com/report".to_string(), "not a url".to_string()];
+        let enriched = enrich_record_with(&rec, &EnricherConfig::default());
+        assert_eq!(enriched.references, ["https://example.com/report"]);
+        assert_eq!(enriched.dropped_references, None);
+        assert!(serde_json::to_value(&enriched).unwrap().get("dropped_references").is_none());
+
+        let config = EnricherConfig::from_params(&params(&[("evidence", "true")])).unwrap();
+        assert_eq!(enrich_record_with(&rec, &config).dropped_references, Some(1));
+    }
+
+    #[test]
+    fn bare_array_of_results_enriches_like_a_pulse() {
+        let mut rec = record("LockBit wave", "Ransomware delivered by phishing emails", &[]);
+        rec.indicators = vec![indicator("Loader", "", None, 1)];
+        let enveloped = run_array_map(&pulse(vec![rec.clone()]));
+
+        let value = serde_json::to_vec(&vec![rec]).unwrap();
+        let bare = array_map(&SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0)).unwrap();
+        assert_eq!(bare.len(), 1);
+        let from_bare: EnrichedThreatRecord = serde_json::from_slice(bare[0].1.as_ref()).unwrap();
+        let from_envelope: EnrichedThreatRecord = serde_json::from_slice(enveloped[0].1.as_ref()).unwrap();
+        assert_eq!(from_bare, from_envelope);
+
+        let value = serde_json::to_vec(&serde_json::json!([{ "id": 7 }])).unwrap();
+        let output = array_map(&SmartModuleRecord::new(fluvio_smartmodule::Record::new(value), 0, 0)).unwrap();
+        let marker: EnrichmentError = serde_json::from_slice(output[0].1.as_ref()).unwrap();
+        assert_eq!(marker.raw_len, r#"{"id":7}"#.len());
+
+        let pulse = parse_pulse(b"[]").unwrap();
+        assert_eq!(pulse.count, 0);
+    }
+
+    #[test]
+    fn source_defaults_to_otx_and_can_be_overridden() {
+        let rec = record("LockBit wave", "ransomware", &[]);
+        let enriched = enrich_record_with(&rec, &EnricherConfig::default());
+        assert_eq!(enriched.source, "otx");
+        assert_eq!(enriched.feed_url, None);
+
+        let config = EnricherConfig::from_params(&params(&[
+            ("source", "partner-feed"),
+            ("feed_url", "https://feeds.example/pulses"),
+        ]))
+        .unwrap();
+        let enriched = enrich_record_with(&rec, &config);
+        assert_eq!(enriched.source, "partner-feed");
+        assert_eq!(enriched.feed_url.as_deref(), Some("https://feeds.example/pulses"));
+        assert!(EnricherConfig::from_params(&params(&[("source", " ")])).is_err());
+    }
+
+    #[test]
+    fn partial_feed_is_set_when_more_pages_follow() {
+        let mut page = pulse(vec![record("LockBit wave", "ransomware", &[])]);
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(run_array_map(&page)[0].1.as_ref()).unwrap();
+        assert!(!enriched.partial_feed);
+
+        page.next = Some("https://otx.alienvault.com/api/v1/pulses/subscribed?page=2".to_string());
+        let enriched: EnrichedThreatRecord = serde_json::from_slice(run_array_map(&page)[0].1.as_ref()).unwrap();
+        assert!(enriched.partial_feed);
+        assert!(enrich_pulse(&page)[0].partial_feed);
+    }
+
+    #[test]
+    fn indicator_scan_cap_prefers_active_indicators() {
+        let mut rec = record("Campaign", "", &[]);
+        rec.indicators = (0..1000)
+            .map(|i| {
+                let (title, active) = if i < 990 { ("ddos botnet node", 0) } else { ("ransomware payload", 1) };
+                let mut ind = indicator(title, "", None, active);
+                ind.id = i;
+                ind
+            })
+            .collect();
+
+        let config = EnricherConfig::from_params(&params(&[("max_indicators_scanned", "10")])).unwrap();
+        assert_eq!(config.max_indicators_scanned, Some(10));
+        let text = FlattenedText::with_limits(&rec, DEFAULT_MAX_TEXT_BYTES, config.max_indicators_scanned);
+        assert_eq!(text.texts(&[TextScope::Indicators]).len(), 10);
+        assert_eq!(labels(&classify_attack_types(&text, THRESHOLD)), vec![AttackType::Ransomware]);
+
+        let text = FlattenedText::with_limits(&rec, DEFAULT_MAX_TEXT_BYTES, None);
+        assert_eq!(text.texts(&[TextScope::Indicators]).len(), 1000);
+        assert_eq!(count_indicators(&rec, SystemTime::now()), (10, 990));
+        assert!(EnricherConfig::from_params(&params(&[("max_indicators_scanned", "all")])).is_err());
+    }
+
+    #[test]
+    fn current_schema_records_round_trip() {
+        let enriched = enriched_fixture();
+        assert_eq!(enriched.schema_version, CURRENT_SCHEMA_VERSION);
+        let value = serde_json::to_value(&enriched).unwrap();
+        assert_eq!(value["schema_version"], CURRENT_SCHEMA_VERSION);
+        assert_eq!(migrate(value).unwrap(), enriched);
+    }
+
+    #[test]
+    fn version_1_records_migrate() {
+        let v1 = serde_json::json!({
+            "attack_types": ["Ransomware", "Phishing"],
+            "attack_vectors": ["Email"],
+            "urgency": ["Hot", "Critical"],
+            "targets": ["Unknown"],
+            "locations": ["Japan"],
+            "expiration_date": "2024-06-01T00:00:00"
+        });
+        let migrated = migrate(v1).unwrap();
+        assert_eq!(migrated.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(migrated.attack_types, [
+            Scored { label: AttackType::Ransomware, confidence: 1.0 },
+            Scored { label: AttackType::Phishing, confidence: 1.0 },
+        ]);
+        assert_eq!(labels(&migrated.attack_vectors), [AttackVector::Email]);
+        assert_eq!(migrated.targets, [Scored::unknown(Target::Unknown)]);
+        assert_eq!(migrated.urgency, UrgencyAssessment { temperature: Temperature::Hot, severity: Severity::Critical });
+        assert_eq!(migrated.locations, ["Japan"]);
+        assert_eq!(migrated.expiration_date, "2024-06-01T00:00:00");
+        assert_eq!(migrated.source, DEFAULT_SOURCE);
+        assert!(migrated.references.is_empty() && migrated.tags.is_empty());
+
+        assert!(migrate(serde_json::json!({ "schema_version": 99 })).is_err());
+        assert!(migrate(serde_json::json!(["Ransomware"])).is_err());
+    }
+
+    #[test]
+    fn provenance_names_the_keyword_behind_each_tag() {
+        let rec = record("LockBit wave", "Files encrypted by ransomware", &[]);
+        let hits = classify_with_provenance(&rec);
+        assert!(hits.contains(&ClassificationHit {
+            category: "attack_type".to_string(),
+            tag: "Ransomware".to_string(),
+            keyword: "ransomware".to_string(),
+        }));
+
+        let rec = record("DNS hijacking campaign", "", &["dns hijacking"]);
+        let hits = classify_with_provenance(&rec);
+        assert_eq!(hits.iter().filter(|hit| hit.tag == "dns_hijacking").count(), 1);
+    }
+
+    #[test]
+    fn provenance_hits_are_omitted_unless_enabled() {
+        let rec = record("", "Files encrypted by ransomware", &[]);
+        let enriched = enrich_record_with(&rec, &EnricherConfig::default());
+        assert!(enriched.hits.is_empty());
+        assert!(serde_json::to_value(&enriched).unwrap().get("hits").is_none());
+
+        let config = EnricherConfig::from_params(&params(&[("include_provenance", "true")])).unwrap();
+        let enriched = enrich_record_with(&rec, &config);
+        assert_eq!(enriched.hits, classify_with_provenance(&rec));
+        assert_eq!(enriched.evidence, None);
+        assert!(EnricherConfig::from_params(&params(&[("include_provenance", "yes")])).is_err());
+    }
+
+    #[test]
+    fn version_2_records_migrate() {
+        let mut v2 = serde_json::to_value(enriched_fixture()).unwrap();
+        v2["schema_version"] = 2.into();
+        v2.as_object_mut().unwrap().remove("hits");
+        v2.as_object_mut().unwrap().remove("pulse_id");
+        v2.as_object_mut().unwrap().remove("pulse_name");
+        let migrated = migrate(v2).unwrap();
+        assert_eq!(migrated.schema_version, CURRENT_SCHEMA_VERSION);
+        assert!(migrated.hits.is_empty());
+        assert_eq!(migrated.pulse_id, migrated.source_id);
+        assert_eq!(migrated.pulse_name, migrated.source_name);
+    }
+
+    #[test]
+    fn days_before_year_counts_leap_days() {
+        assert_eq!(days_before_year(1970), 0);
+        assert_eq!(days_before_year(1973), 365 * 3 + 1);
+        for year in [2000, 2001, 2100, 2401] {
+            assert_eq!(days_before_year(year), (1970..year).map(days_in_year).sum::<u64>());
+        }
+    }
+
+    #[test]
+    fn folding_for_matching_borrows_plain_ascii() {
+        assert!(matches!(fold_for_matching("RaNsOmWaRe outbreak"), std::borrow::Cow::Borrowed("RaNsOmWaRe outbreak")));
+        assert_eq!(fold_for_matching("Spear-Phishing\u{200B}  KIT "), "Spear Phishing KIT");
+        assert_eq!(fold_for_matching("ФИШИНГ İ"), "фишинг i");
+
+        let rec = record("RaNsOmWaRe outbreak", "", &["LockBit"]);
+        let text = FlattenedText::new(&rec);
+        assert!(text.fields.iter().all(|(_, field)| matches!(field, std::borrow::Cow::Borrowed(_))));
+        assert_eq!(labels(&classify_attack_types(&text, THRESHOLD)), vec![AttackType::Ransomware]);
+    }
+
+    #[test]
+    fn fallback_record_key_is_pinned() {
+        let mut rec = record("Anonymous pulse", "", &[]);
+        rec.id = String::new();
+        assert_eq!(record_key(&rec).as_ref(), b"f3ce6da937d1efd3");
+        assert_eq!(fnv1a([]), 0xcbf2_9ce4_8422_2325);
+    }
+
+    #[test]
+    fn enrich_record_with_uses_the_given_config() {
+        let mut rec = record("Campaign", "A locker spreading over smb", &[]);
+        rec.indicators = (0..3).map(|_| dated_indicator("2024-01-01T00:00:00", 1)).collect();
+        let enriched = enrich_record_with(&rec, &EnricherConfig::default());
+        assert_eq!(labels(&enriched.attack_types), vec![AttackType::Unknown]);
+        assert_eq!(enriched.urgency.temperature, Temperature::Cold);
+
+        let config = EnricherConfig {
+            classification_threshold: 0.3,
+            hot_window_days: 100 * 365,
+            evidence: true,
+            ..EnricherConfig::default()
+        };
+        let enriched = enrich_record_with(&rec, &config);
+        assert_eq!(labels(&enriched.attack_types), vec![AttackType::Ransomware]);
+        assert_eq!(enriched.urgency.temperature, Temperature::Hot);
+        assert!(enriched.evidence.unwrap().iter().any(|hit| hit.keyword == "locker"));
+
+        let config = EnricherConfig { max_text_bytes: 8, ..EnricherConfig::default() };
+        assert!(enrich_record_with(&rec, &config).truncated);
+    }
+
+    #[test]
+    fn evidence_only_backs_kept_classifications() {
+        let config = EnricherConfig { evidence: true, ..EnricherConfig::default() };
+        let rec = record("", "Visitors of the web portal were redirected by ransomware", &[]);
+        let enriched = enrich_record_with(&rec, &config);
+        assert_eq!(labels(&enriched.targets), vec![Target::Unknown]);
+        let evidence = enriched.evidence.unwrap();
+        assert!(evidence.iter().all(|hit| !matches!(hit.classification, Classification::Target(_))));
+        assert!(evidence.iter().any(|hit| hit.classification == Classification::AttackType(AttackType::Ransomware)));
+        assert!(collect_evidence(&FlattenedText::new(&rec), THRESHOLD)
+            .iter()
+            .any(|hit| hit.classification == Classification::Target(Target::WebApp)));
+
+        let rec = record("", "A critical flaw, rated low by some", &[]);
+        let evidence = enrich_record_with(&rec, &config).evidence.unwrap();
+        assert!(evidence.iter().any(|hit| hit.classification == Classification::Urgency(Urgency::Critical)));
+        assert!(evidence.iter().all(|hit| hit.classification != Classification::Urgency(Urgency::Low)));
+    }
+
+    #[test]
+    fn provenance_covers_only_kept_labels() {
+        let rec = record("", "Visitors of the web portal were redirected by ransomware", &[]);
+        let hits = classify_with_provenance(&rec);
+        assert!(hits.iter().all(|hit| hit.category != "target"));
+        assert!(hits.iter().any(|hit| hit.tag == "Ransomware" && hit.keyword == "ransomware"));
+    }
+
+    #[test]
+    fn provenance_names_the_source_of_labels_without_keywords() {
+        let mut rec = record("Weekly roundup", "Activity observed", &[]);
+        rec.malware_families = vec!["LockBit".to_string()];
+        rec.attack_ids = vec!["T1566".to_string()];
+        rec.indicators = vec![typed_indicator("URL")];
+        let hits = classify_with_provenance(&rec);
+        let source = |category: &str, tag: &str| {
+            hits.iter().find(|hit| hit.category == category && hit.tag == tag).map(|hit| hit.keyword.as_str())
+        };
+        assert_eq!(source("attack_type", "Ransomware"), Some("malware_family:lockbit"));
+        assert_eq!(source("attack_type", "Phishing"), Some("technique:T1566"));
+        assert_eq!(source("attack_vector", "WebApplication"), Some("indicator_type:url"));
+        assert_eq!(source("attack_vector", "Email"), Some("attack_type:phishing"));
+        assert_eq!(source("target", "EmailAttack"), Some("attack_type:phishing"));
+
+        let mut rec = record("Patch now", "See CVE-2019-0708", &[]);
+        rec.modified = "2024-03-01T00:00:00".to_string();
+        let hits = classify_with_provenance(&rec);
+        assert!(hits.contains(&ClassificationHit {
+            category: "attack_type".to_string(),
+            tag: "Exploit".to_string(),
+            keyword: "cve_year:2019".to_string(),
+        }));
+    }
+}